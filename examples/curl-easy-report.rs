@@ -5,7 +5,7 @@ use threescalers::{
     extensions::{self, Extension},
     http::{
         request::{curl::CurlEasyClient, SetupRequest},
-        Request,
+        Backend, Request,
     },
     service::*,
     transaction::Transaction,
@@ -89,7 +89,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 fn run_request(request: Request) -> Result<(), Box<dyn Error>> {
     let mut client = Easy::new();
     client.verbose(true).unwrap();
-    let curlclient = client.setup_request(request, "https://echo-api.3scale.net")?;
+    let curlclient = client.setup_request(request, &Backend::new("echo-api.3scale.net"))?;
     let result = exec_request(&curlclient);
     show_response(curlclient, result).map_err(Into::into)
 }
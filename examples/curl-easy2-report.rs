@@ -3,7 +3,7 @@ use threescalers::{
     application::*,
     credentials::*,
     extensions::{self, Extension},
-    http::{request::SetupRequest, Request},
+    http::{request::SetupRequest, Backend, Request},
     service::*,
     transaction::Transaction,
     usage::Usage,
@@ -88,7 +88,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 fn run_request(request: Request) -> Result<(), Box<dyn Error>> {
     let mut client = Easy2::new(BodyHandle::new());
     client.verbose(true).unwrap();
-    client.setup_request(request, "https://echo-api.3scale.net")?;
+    client.setup_request(request, &Backend::new("echo-api.3scale.net"))?;
     let result = exec_request(&client);
     show_response(client, result).map_err(Into::into)
 }
@@ -0,0 +1,73 @@
+// Reports problems in the mapping rules of 3scale services.
+//
+// Takes the path to either an Apicast configuration file or a proxy configuration as returned
+// by System, and exits with an error status if any rule is identical to another one, cannot
+// be reached or cannot be analyzed. Overlapping rules are only listed, as they are often
+// intended, ie. with a catch-all rule.
+use threescalers::{
+    config::{Config, ProxyConfig, ServiceConfig},
+    http::mapping_rule::{Finding, MappingRule},
+};
+
+use std::{error::Error, process::ExitCode};
+
+fn describe(rule: &MappingRule) -> String {
+    format!(
+        "{} {} => {} +{}{}",
+        rule.rule().method().as_str(),
+        rule.rule().pattern(),
+        rule.metric(),
+        rule.delta(),
+        if rule.is_last() { " (last)" } else { "" }
+    )
+}
+
+fn lint(service: &ServiceConfig) -> bool {
+    let rules = service.rules().as_slice();
+    let mut ok = true;
+
+    for finding in service.rules().analyze() {
+        let (severity, involved) = match finding {
+            Finding::Overlapping { first, second } => ("warning", [Some(first), Some(second)]),
+            Finding::Identical { first, second } => ("error", [Some(first), Some(second)]),
+            Finding::Shadowed { rule, by } => ("warning", [Some(by), Some(rule)]),
+            Finding::Unreachable { rule, by } => ("error", [Some(by), Some(rule)]),
+            Finding::Unsupported { rule } => ("error", [Some(rule), None]),
+            _ => ("warning", [None, None]),
+        };
+        ok &= severity != "error";
+
+        println!(
+            "{}: service {}: {}",
+            severity,
+            service.service_id().as_ref(),
+            finding
+        );
+        for idx in involved.into_iter().flatten() {
+            println!("    #{}: {}", idx, describe(&rules[idx]));
+        }
+    }
+
+    ok
+}
+
+fn main() -> Result<ExitCode, Box<dyn Error>> {
+    let path = std::env::args()
+        .nth(1)
+        .ok_or("usage: mapping-rules-lint <config.json>")?;
+    let contents = std::fs::read_to_string(path)?;
+
+    let services = match serde_json::from_str::<Config>(&contents) {
+        Ok(config) => config.into_services(),
+        Err(_) => vec![serde_json::from_str::<ProxyConfig>(&contents)?.into_service_config()],
+    };
+
+    // lint every service rather than stopping at the first one with errors
+    let failed = services.iter().filter(|service| !lint(service)).count();
+
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
@@ -3,7 +3,7 @@ use threescalers::{
     application::*,
     credentials::*,
     extensions::{self, Extension},
-    http::{request::SetupRequest, Request},
+    http::{request::SetupRequest, Backend, Request},
     service::*,
     transaction::Transaction,
     usage::Usage,
@@ -85,7 +85,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
 fn run_request(request: Request) -> Result<Response, Box<dyn Error>> {
     let mut client = Client::new();
-    let reqbuilder = client.setup_request(request, "https://echo-api.3scale.net")?;
+    let reqbuilder = client.setup_request(request, &Backend::new("echo-api.3scale.net"))?;
     let result = exec_request(reqbuilder);
     show_response(result).map_err(Into::into)
 }
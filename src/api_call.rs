@@ -1,10 +1,10 @@
 use std::prelude::v1::*;
 
-use crate::{anyhow, Error};
+use crate::Error;
 
 use crate::{
-    application::Application, extensions::List, service::Service, transaction::Transaction,
-    usage::Usage, user::User,
+    application::Application, extensions::List, http::request::HeaderPolicy, service::Service,
+    transaction::Transaction, usage::Usage, user::User,
 };
 
 use crate::ToParams;
@@ -30,6 +30,7 @@ pub struct ApiCall<'a> {
     service: &'a Service,
     transactions: &'a [Transaction<'a>],
     extensions: Option<&'a List<'a>>,
+    header_policy: Option<&'a HeaderPolicy>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -38,6 +39,7 @@ pub struct Builder<'a> {
     kind: Option<Kind>,
     transactions: &'a [Transaction<'a>],
     extensions: Option<&'a List<'a>>,
+    header_policy: Option<&'a HeaderPolicy>,
 }
 
 // TODO: we can improve this with a state machine of types so that we are required to set svc, app,
@@ -49,6 +51,7 @@ impl<'a> Builder<'a> {
             kind: Default::default(),
             transactions: Default::default(),
             extensions: Default::default(),
+            header_policy: Default::default(),
         }
     }
 
@@ -72,14 +75,18 @@ impl<'a> Builder<'a> {
         self
     }
 
-    pub fn build(&self) -> Result<ApiCall, Error> {
-        let kind = self.kind.ok_or_else(|| anyhow!("kind error"))?;
-        Ok(ApiCall::new(
-            kind,
-            self.service,
-            self.transactions,
-            self.extensions,
-        ))
+    /// Sets the headers to send along with the call. `HeaderPolicy::default()` is used otherwise.
+    pub fn header_policy(&mut self, policy: &'a HeaderPolicy) -> &mut Self {
+        self.header_policy = Some(policy);
+        self
+    }
+
+    pub fn build(&self) -> Result<ApiCall<'_>, Error> {
+        let kind = self.kind.ok_or(Error::MissingKind)?;
+        let mut apicall = ApiCall::new(kind, self.service, self.transactions, self.extensions);
+        apicall.header_policy = self.header_policy;
+
+        Ok(apicall)
     }
 }
 
@@ -101,6 +108,7 @@ impl<'a> ApiCall<'a> {
             service,
             transactions,
             extensions,
+            header_policy: None,
         }
     }
 
@@ -136,14 +144,18 @@ impl<'a> ApiCall<'a> {
         self.transaction().and_then(Transaction::user)
     }
 
-    pub fn usage(&self) -> Option<&Usage> {
+    pub fn usage(&self) -> Option<&Usage<'_>> {
         self.transaction().and_then(Transaction::usage)
     }
 
-    pub fn extensions(&self) -> Option<&List> {
+    pub fn extensions(&self) -> Option<&List<'_>> {
         self.extensions
     }
 
+    pub fn header_policy(&self) -> Option<&HeaderPolicy> {
+        self.header_policy
+    }
+
     pub fn params(&self) -> Vec<(Cow<'_, str>, &str)> {
         let mut params = Vec::with_capacity(8);
 
@@ -0,0 +1,9 @@
+// Local caches built out of Apisonator responses, allowing some decisions to be taken without
+// calling the backend. These are sans-IO too: time is always passed in by the caller as a Unix
+// timestamp in seconds, and no locking is performed.
+mod app_keys;
+mod fallback;
+pub use app_keys::{AppKeysCache, KeyCheck, RefreshPolicy};
+pub use fallback::{
+    AuthorizationCache, Decision, FailureMode, FailurePolicy, PendingReport, PendingUsage, Reason,
+};
@@ -0,0 +1,335 @@
+use std::prelude::v1::*;
+
+use std::collections::BTreeMap;
+
+use crate::{
+    application::{AppKey, Application},
+    response::{AuthorizationStatus, ListAppKeys},
+};
+
+/// Outcome of checking an application's key against the cache.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KeyCheck {
+    /// The key is one of the application's keys, or no key was given to an application that
+    /// has none.
+    Valid,
+    /// The key is not one of the application's keys, or it is missing.
+    Invalid,
+    /// The application's keys are not cached, so the backend has to be asked.
+    Missing,
+    /// The cached keys are too old to be trusted, so the backend has to be asked.
+    Stale,
+    /// The application is not identified by an app_id, so there are no keys to check.
+    NotAppId,
+}
+
+/// Settings controlling when cached keys have to be refreshed from the backend.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RefreshPolicy {
+    ttl: i64,
+    unknown_key_refresh: Option<i64>,
+}
+
+impl RefreshPolicy {
+    /// Creates a policy considering keys stale once they are older than `ttl` seconds.
+    pub fn new(ttl: i64) -> Self {
+        Self {
+            ttl,
+            unknown_key_refresh: None,
+        }
+    }
+
+    /// Reports unknown keys as `Stale` rather than `Invalid` when the cached keys are older than
+    /// `seconds`, so that keys recently added to the application are picked up before the TTL
+    /// expires.
+    pub fn refresh_unknown_keys_after(mut self, seconds: i64) -> Self {
+        self.unknown_key_refresh = Some(seconds);
+        self
+    }
+
+    pub fn ttl(&self) -> i64 {
+        self.ttl
+    }
+
+    pub fn unknown_key_refresh(&self) -> Option<i64> {
+        self.unknown_key_refresh
+    }
+}
+
+impl Default for RefreshPolicy {
+    fn default() -> Self {
+        Self::new(60)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    keys: Vec<AppKey>,
+    updated_at: i64,
+}
+
+/// A cache of application keys per service and application, fed from `list_app_keys` results.
+#[derive(Debug, Clone, Default)]
+pub struct AppKeysCache {
+    policy: RefreshPolicy,
+    entries: BTreeMap<(String, String), Entry>,
+}
+
+impl AppKeysCache {
+    pub fn new(policy: RefreshPolicy) -> Self {
+        Self {
+            policy,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    pub fn policy(&self) -> &RefreshPolicy {
+        &self.policy
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Stores the keys of an application, replacing any previous ones.
+    pub fn insert_keys<S, A, I>(&mut self, service_id: S, app_id: A, keys: I, now: i64)
+    where
+        S: AsRef<str>,
+        A: AsRef<str>,
+        I: IntoIterator<Item = AppKey>,
+    {
+        self.entries.insert(
+            (service_id.as_ref().to_owned(), app_id.as_ref().to_owned()),
+            Entry {
+                keys: keys.into_iter().collect(),
+                updated_at: now,
+            },
+        );
+    }
+
+    /// Stores a `ListAppKeys` result. Returns `false` if it does not specify both the service
+    /// and the application it belongs to, in which case `insert_keys` should be used instead.
+    pub fn insert(&mut self, list: &ListAppKeys, now: i64) -> bool {
+        match (list.service_id(), list.app_id()) {
+            (Some(service_id), Some(app_id)) => {
+                self.insert_keys(service_id, app_id, list.keys().iter().cloned(), now);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Stores the keys listed in an authorization response, if any.
+    pub fn update(&mut self, status: &AuthorizationStatus, now: i64) -> bool {
+        status
+            .app_keys()
+            .map_or(false, |list| self.insert(list, now))
+    }
+
+    pub fn remove<S: AsRef<str>, A: AsRef<str>>(&mut self, service_id: S, app_id: A) -> bool {
+        self.entries
+            .remove(&(service_id.as_ref().to_owned(), app_id.as_ref().to_owned()))
+            .is_some()
+    }
+
+    /// Removes the entries that are stale according to the refresh policy.
+    pub fn purge_stale(&mut self, now: i64) -> usize {
+        let before = self.len();
+        let ttl = self.policy.ttl;
+
+        self.entries
+            .retain(|_, entry| now.saturating_sub(entry.updated_at) <= ttl);
+        before - self.len()
+    }
+
+    /// Checks the key of an `Application::AppId` against the cached keys.
+    ///
+    /// The comparison of keys takes the same time regardless of where they differ and of which
+    /// of the application's keys matches, if any.
+    pub fn check<S: AsRef<str>>(
+        &self,
+        service_id: S,
+        application: &Application,
+        now: i64,
+    ) -> KeyCheck {
+        let (app_id, app_key) = match application {
+            Application::AppId(app_id, app_key) => (app_id, app_key),
+            _ => return KeyCheck::NotAppId,
+        };
+
+        let entry = match self
+            .entries
+            .get(&(service_id.as_ref().to_owned(), app_id.as_ref().to_owned()))
+        {
+            Some(entry) => entry,
+            None => return KeyCheck::Missing,
+        };
+
+        let age = now.saturating_sub(entry.updated_at);
+        if age > self.policy.ttl {
+            return KeyCheck::Stale;
+        }
+
+        let valid = match app_key {
+            Some(app_key) => entry.keys.iter().fold(false, |found, key| {
+                found | constant_time_eq(key.as_ref().as_bytes(), app_key.as_ref().as_bytes())
+            }),
+            None => entry.keys.is_empty(),
+        };
+
+        if valid {
+            KeyCheck::Valid
+        } else if app_key.is_some()
+            && self
+                .policy
+                .unknown_key_refresh
+                .map_or(false, |refresh| age > refresh)
+        {
+            KeyCheck::Stale
+        } else {
+            KeyCheck::Invalid
+        }
+    }
+}
+
+// Compares two byte strings in a time that only depends on their lengths.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::str::FromStr;
+
+    fn cache_with_keys(policy: RefreshPolicy, keys: &[&str], now: i64) -> AppKeysCache {
+        let mut cache = AppKeysCache::new(policy);
+        let list = ListAppKeys::new(Some("a_service"), Some("an_app"), keys.iter().copied());
+
+        assert!(cache.insert(&list, now));
+        cache
+    }
+
+    #[test]
+    fn check_app_keys() {
+        let cache = cache_with_keys(RefreshPolicy::new(60), &["key1", "key2"], 1000);
+
+        let check = |app: Application| cache.check("a_service", &app, 1030);
+
+        assert_eq!(
+            check(Application::from_app_id_and_key("an_app", "key2")),
+            KeyCheck::Valid
+        );
+        assert_eq!(
+            check(Application::from_app_id_and_key("an_app", "key3")),
+            KeyCheck::Invalid
+        );
+        assert_eq!(
+            check(Application::from_app_id_and_key("an_app", "key")),
+            KeyCheck::Invalid
+        );
+        assert_eq!(check(Application::from_app_id("an_app")), KeyCheck::Invalid);
+        assert_eq!(
+            check(Application::from_app_id_and_key("other_app", "key1")),
+            KeyCheck::Missing
+        );
+        assert_eq!(
+            check(Application::from_user_key("key1")),
+            KeyCheck::NotAppId
+        );
+        assert_eq!(
+            cache.check(
+                "other_service",
+                &Application::from_app_id_and_key("an_app", "key1"),
+                1030
+            ),
+            KeyCheck::Missing
+        );
+    }
+
+    #[test]
+    fn apps_without_keys_require_no_key() {
+        let cache = cache_with_keys(RefreshPolicy::new(60), &[], 1000);
+
+        assert_eq!(
+            cache.check("a_service", &Application::from_app_id("an_app"), 1000),
+            KeyCheck::Valid
+        );
+        assert_eq!(
+            cache.check(
+                "a_service",
+                &Application::from_app_id_and_key("an_app", "k"),
+                1000
+            ),
+            KeyCheck::Invalid
+        );
+    }
+
+    #[test]
+    fn refresh_policy() {
+        let policy = RefreshPolicy::new(60).refresh_unknown_keys_after(10);
+        let mut cache = cache_with_keys(policy, &["key1"], 1000);
+        let known = Application::from_app_id_and_key("an_app", "key1");
+        let unknown = Application::from_app_id_and_key("an_app", "key2");
+
+        assert_eq!(cache.check("a_service", &unknown, 1005), KeyCheck::Invalid);
+        assert_eq!(cache.check("a_service", &unknown, 1011), KeyCheck::Stale);
+        assert_eq!(cache.check("a_service", &known, 1060), KeyCheck::Valid);
+        assert_eq!(cache.check("a_service", &known, 1061), KeyCheck::Stale);
+
+        assert_eq!(cache.purge_stale(1060), 0);
+        assert_eq!(cache.purge_stale(1061), 1);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn update_from_authorization() {
+        use crate::response::Authorization;
+
+        let xml_response = r##"<?xml version="1.0" encoding="UTF-8"?>
+        <status>
+            <authorized>true</authorized>
+            <plan>Basic</plan>
+            <app_keys app="an_app" svc="a_service">
+                <key id="a_secret_key" />
+            </app_keys>
+        </status>
+        "##;
+        let status = Authorization::from_str(xml_response)
+            .unwrap()
+            .into_inner()
+            .unwrap();
+        let mut cache = AppKeysCache::default();
+
+        assert!(cache.update(&status, 0));
+        assert_eq!(
+            cache.check(
+                "a_service",
+                &Application::from_app_id_and_key("an_app", "a_secret_key"),
+                0
+            ),
+            KeyCheck::Valid
+        );
+
+        let list = ListAppKeys::new(None::<&str>, Some("an_app"), ["key"]);
+        assert!(!cache.insert(&list, 0));
+        assert!(cache.remove("a_service", "an_app"));
+        assert!(!cache.remove("a_service", "an_app"));
+    }
+
+    #[test]
+    fn constant_time_comparison() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"abcd"));
+        assert!(constant_time_eq(b"", b""));
+    }
+}
@@ -0,0 +1,603 @@
+use std::prelude::v1::*;
+
+use core::fmt;
+use std::collections::BTreeMap;
+
+use crate::{application::Application, response::AuthorizationStatus, usage::Usage};
+
+// Applications are told apart by their kind of credentials and their values.
+type AppRef = (u8, String, Option<String>);
+
+fn app_ref(application: &Application) -> AppRef {
+    match application {
+        Application::AppId(app_id, app_key) => (
+            0,
+            app_id.as_ref().to_owned(),
+            app_key.as_ref().map(|key| key.as_ref().to_owned()),
+        ),
+        Application::UserKey(user_key) => (1, user_key.as_ref().to_owned(), None),
+        Application::OAuthToken(token) => (2, token.as_ref().to_owned(), None),
+    }
+}
+
+// Reports identify applications by their app_id only.
+fn without_key(application: &Application) -> Application {
+    match application {
+        Application::AppId(app_id, Some(_)) => Application::AppId(app_id.clone(), None),
+        application => application.clone(),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedStatus {
+    status: AuthorizationStatus,
+    updated_at: i64,
+}
+
+/// The last known authorization status of applications, per service.
+///
+/// Applications identified by an app_id are cached along with their key, so that a status
+/// obtained with a valid key is not used to let requests with other keys through.
+#[derive(Debug, Clone, Default)]
+pub struct AuthorizationCache {
+    entries: BTreeMap<(String, AppRef), CachedStatus>,
+}
+
+impl AuthorizationCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Stores the status the backend answered with for an application.
+    pub fn insert<S: AsRef<str>>(
+        &mut self,
+        service_id: S,
+        application: &Application,
+        status: AuthorizationStatus,
+        now: i64,
+    ) {
+        self.entries.insert(
+            (service_id.as_ref().to_owned(), app_ref(application)),
+            CachedStatus {
+                status,
+                updated_at: now,
+            },
+        );
+    }
+
+    /// Returns the cached status of an application along with its age in seconds.
+    pub fn get<S: AsRef<str>>(
+        &self,
+        service_id: S,
+        application: &Application,
+        now: i64,
+    ) -> Option<(&AuthorizationStatus, i64)> {
+        self.entries
+            .get(&(service_id.as_ref().to_owned(), app_ref(application)))
+            .map(|entry| (&entry.status, now.saturating_sub(entry.updated_at)))
+    }
+
+    pub fn remove<S: AsRef<str>>(&mut self, service_id: S, application: &Application) -> bool {
+        self.entries
+            .remove(&(service_id.as_ref().to_owned(), app_ref(application)))
+            .is_some()
+    }
+
+    /// Removes the entries older than `max_age` seconds.
+    pub fn purge_older_than(&mut self, max_age: i64, now: i64) -> usize {
+        let before = self.len();
+
+        self.entries
+            .retain(|_, entry| now.saturating_sub(entry.updated_at) <= max_age);
+        before - self.len()
+    }
+}
+
+/// Usage of an application that could not be reported to the backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingReport {
+    service_id: String,
+    application: Application,
+    usage: Vec<(String, String)>,
+}
+
+impl PendingReport {
+    pub fn service_id(&self) -> &str {
+        self.service_id.as_str()
+    }
+
+    /// The application, without its key if it had one, as reports do not need it.
+    pub fn application(&self) -> &Application {
+        &self.application
+    }
+
+    /// The accumulated usage, suitable for `Usage::new`.
+    pub fn usage(&self) -> &[(String, String)] {
+        self.usage.as_slice()
+    }
+}
+
+/// Usage accumulated per service and application while the backend is unreachable, to be
+/// reported once it is back.
+#[derive(Debug, Clone, Default)]
+pub struct PendingUsage {
+    entries: BTreeMap<(String, AppRef), (Application, BTreeMap<String, u64>)>,
+}
+
+impl PendingUsage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds usage of an application. Values that are not plain numbers of hits, such as the
+    /// `#`-prefixed ones setting a metric to a value, cannot be accumulated and are ignored.
+    pub fn record<S: AsRef<str>>(
+        &mut self,
+        service_id: S,
+        application: &Application,
+        usage: &Usage,
+    ) {
+        let application = without_key(application);
+        let (_, metrics) = self
+            .entries
+            .entry((service_id.as_ref().to_owned(), app_ref(&application)))
+            .or_insert_with(|| (application, BTreeMap::new()));
+
+        for mu in usage.as_vec() {
+            if let Ok(hits) = mu.value().parse::<u64>() {
+                let value = metrics.entry(mu.metric().to_owned()).or_insert(0);
+                *value = value.saturating_add(hits);
+            }
+        }
+    }
+
+    /// The hits accumulated for a metric of an application.
+    pub fn hits<S: AsRef<str>>(
+        &self,
+        service_id: S,
+        application: &Application,
+        metric: &str,
+    ) -> u64 {
+        self.metrics(service_id.as_ref(), application)
+            .and_then(|metrics| metrics.get(metric))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn metrics(
+        &self,
+        service_id: &str,
+        application: &Application,
+    ) -> Option<&BTreeMap<String, u64>> {
+        self.entries
+            .get(&(service_id.to_owned(), app_ref(&without_key(application))))
+            .map(|(_, metrics)| metrics)
+    }
+
+    /// Takes all the pending usage out, leaving nothing pending.
+    pub fn drain(&mut self) -> Vec<PendingReport> {
+        core::mem::take(&mut self.entries)
+            .into_iter()
+            .map(|((service_id, _), (application, metrics))| PendingReport {
+                service_id,
+                application,
+                usage: metrics
+                    .into_iter()
+                    .map(|(metric, hits)| (metric, hits.to_string()))
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// What to do when there is no usable authorization status for an application.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FailureMode {
+    /// Let requests through.
+    Open,
+    /// Deny requests.
+    Closed,
+}
+
+/// The reason for a decision taken while the backend is unreachable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reason {
+    /// The last known status authorized the application and its limits allow the usage.
+    CachedAuthorization { age: i64 },
+    /// The last known status denied the application.
+    CachedDenial { age: i64, reason: Option<String> },
+    /// The usage, on top of the pending one, would exceed the last known limits of a metric.
+    LimitsExceeded { age: i64, metric: String },
+    /// There was no usable cached status, so the failure mode applied.
+    NoCachedStatus(FailureMode),
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::CachedAuthorization { age } => {
+                write!(f, "authorized by a status cached {}s ago", age)
+            }
+            Reason::CachedDenial { age, reason } => write!(
+                f,
+                "denied by a status cached {}s ago: {}",
+                age,
+                reason.as_deref().unwrap_or("no reason given")
+            ),
+            Reason::LimitsExceeded { age, metric } => write!(
+                f,
+                "limits of metric {} cached {}s ago would be exceeded",
+                metric, age
+            ),
+            Reason::NoCachedStatus(FailureMode::Open) => {
+                f.write_str("no cached status, failing open")
+            }
+            Reason::NoCachedStatus(FailureMode::Closed) => {
+                f.write_str("no cached status, failing closed")
+            }
+        }
+    }
+}
+
+/// Whether to let a request through while the backend is unreachable, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    allowed: bool,
+    reason: Reason,
+}
+
+impl Decision {
+    pub fn is_allowed(&self) -> bool {
+        self.allowed
+    }
+
+    pub fn reason(&self) -> &Reason {
+        &self.reason
+    }
+}
+
+/// Decides whether to let requests through when the backend cannot be reached.
+///
+/// The last known authorization status of the application is used as long as it is recent
+/// enough, and its usage limits are checked against the usage pending to be reported. Otherwise
+/// the failure mode applies.
+///
+/// # Examples
+///
+/// ```
+/// use threescalers::{
+///     application::Application,
+///     cache::{AuthorizationCache, FailurePolicy, PendingUsage, Reason},
+///     usage::Usage,
+/// };
+///
+/// let policy = FailurePolicy::fail_open().cached_for(300);
+/// let cache = AuthorizationCache::new();
+/// let mut pending = PendingUsage::new();
+/// let app = Application::from_user_key("a_user_key");
+/// let hits = [("hits", "1")];
+/// let usage = Usage::new(&hits);
+///
+/// let decision = policy.decide(&cache, &mut pending, "a_service", &app, Some(&usage), 1000);
+///
+/// assert!(decision.is_allowed());
+/// assert!(matches!(decision.reason(), Reason::NoCachedStatus(_)));
+/// assert_eq!(pending.drain()[0].usage(), [("hits".to_string(), "1".to_string())]);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FailurePolicy {
+    mode: FailureMode,
+    max_age: Option<i64>,
+    enforce_limits: bool,
+}
+
+impl FailurePolicy {
+    /// Creates a policy ignoring cached statuses and applying the given failure mode.
+    pub fn new(mode: FailureMode) -> Self {
+        Self {
+            mode,
+            max_age: None,
+            enforce_limits: true,
+        }
+    }
+
+    pub fn fail_open() -> Self {
+        Self::new(FailureMode::Open)
+    }
+
+    pub fn fail_closed() -> Self {
+        Self::new(FailureMode::Closed)
+    }
+
+    /// Uses cached statuses up to `seconds` old.
+    pub fn cached_for(mut self, seconds: i64) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    /// Whether to deny usage exceeding the limits of cached statuses. Enabled by default.
+    pub fn enforce_limits(mut self, enforce_limits: bool) -> Self {
+        self.enforce_limits = enforce_limits;
+        self
+    }
+
+    pub fn mode(&self) -> FailureMode {
+        self.mode
+    }
+
+    pub fn max_age(&self) -> Option<i64> {
+        self.max_age
+    }
+
+    /// Decides whether to let a request with the given usage through, recording the usage as
+    /// pending when it is.
+    pub fn decide<S: AsRef<str>>(
+        &self,
+        cache: &AuthorizationCache,
+        pending: &mut PendingUsage,
+        service_id: S,
+        application: &Application,
+        usage: Option<&Usage>,
+        now: i64,
+    ) -> Decision {
+        let service_id = service_id.as_ref();
+        let cached = self.max_age.and_then(|max_age| {
+            cache
+                .get(service_id, application, now)
+                .filter(|&(_, age)| age <= max_age)
+        });
+
+        let reason = match cached {
+            None => Reason::NoCachedStatus(self.mode),
+            Some((status, age)) if !status.is_authorized() => Reason::CachedDenial {
+                age,
+                reason: status.reason().map(ToOwned::to_owned),
+            },
+            Some((status, age)) => {
+                match self.exceeded_metric(status, pending, service_id, application, usage) {
+                    Some(metric) => Reason::LimitsExceeded { age, metric },
+                    None => Reason::CachedAuthorization { age },
+                }
+            }
+        };
+
+        let allowed = match reason {
+            Reason::CachedAuthorization { .. } => true,
+            Reason::NoCachedStatus(mode) => mode == FailureMode::Open,
+            _ => false,
+        };
+
+        if allowed {
+            if let Some(usage) = usage {
+                pending.record(service_id, application, usage);
+            }
+        }
+
+        Decision { allowed, reason }
+    }
+
+    // Returns the first limited metric that the usage would exceed, counting the usage of
+    // children metrics towards their parents.
+    fn exceeded_metric(
+        &self,
+        status: &AuthorizationStatus,
+        pending: &PendingUsage,
+        service_id: &str,
+        application: &Application,
+        usage: Option<&Usage>,
+    ) -> Option<String> {
+        if !self.enforce_limits {
+            return None;
+        }
+
+        let reports = status.usage_reports()?;
+        let metrics = usage.map_or(&[][..], |usage| usage.as_vec().as_slice());
+
+        reports.iter().find_map(|report| {
+            let counts = |metric: &str| {
+                metric == report.metric()
+                    || status
+                        .hierarchy()
+                        .and_then(|h| h.parent_of(metric))
+                        .map_or(false, |parent| parent == report.metric())
+            };
+            let requested = metrics
+                .iter()
+                .filter(|mu| counts(mu.metric()))
+                .filter_map(|mu| mu.value().parse::<u64>().ok())
+                .fold(0u64, u64::saturating_add);
+            let hits = pending
+                .metrics(service_id, application)
+                .into_iter()
+                .flatten()
+                .filter(|(metric, _)| counts(metric))
+                .fold(requested, |acc, (_, hits)| acc.saturating_add(*hits));
+
+            report
+                .authorize(hits)
+                .is_err()
+                .then(|| report.metric().to_owned())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::Authorization;
+    use core::str::FromStr;
+
+    const AUTHORIZED: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+        <status>
+            <authorized>true</authorized>
+            <plan>Basic</plan>
+            <usage_reports>
+                <usage_report metric="hits" period="minute">
+                    <period_start>2019-06-05 16:24:00 +0000</period_start>
+                    <period_end>2019-06-05 16:25:00 +0000</period_end>
+                    <max_value>10</max_value>
+                    <current_value>5</current_value>
+                </usage_report>
+            </usage_reports>
+            <hierarchy>
+                <metric name="hits" children="products" />
+            </hierarchy>
+        </status>"##;
+
+    const DENIED: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+        <status>
+            <authorized>false</authorized>
+            <reason>application key is invalid</reason>
+            <plan>Basic</plan>
+        </status>"##;
+
+    fn status(xml: &str) -> AuthorizationStatus {
+        Authorization::from_str(xml).unwrap().into_inner().unwrap()
+    }
+
+    #[test]
+    fn fail_open_or_closed_without_cached_status() {
+        let cache = AuthorizationCache::new();
+        let mut pending = PendingUsage::new();
+        let app = Application::from_user_key("a_user_key");
+
+        let decision =
+            FailurePolicy::fail_closed().decide(&cache, &mut pending, "a_service", &app, None, 0);
+        assert!(!decision.is_allowed());
+        assert_eq!(
+            decision.reason(),
+            &Reason::NoCachedStatus(FailureMode::Closed)
+        );
+
+        let decision =
+            FailurePolicy::fail_open().decide(&cache, &mut pending, "a_service", &app, None, 0);
+        assert!(decision.is_allowed());
+        assert_eq!(
+            decision.reason().to_string(),
+            "no cached status, failing open"
+        );
+    }
+
+    #[test]
+    fn use_cached_statuses() {
+        let policy = FailurePolicy::fail_closed().cached_for(60);
+        let mut cache = AuthorizationCache::new();
+        let mut pending = PendingUsage::new();
+        let good = Application::from_app_id_and_key("an_app", "a_key");
+        let bad = Application::from_app_id_and_key("an_app", "another_key");
+        cache.insert("a_service", &good, status(AUTHORIZED), 1000);
+        cache.insert("a_service", &bad, status(DENIED), 1000);
+
+        let decision = policy.decide(&cache, &mut pending, "a_service", &good, None, 1030);
+        assert!(decision.is_allowed());
+        assert_eq!(decision.reason(), &Reason::CachedAuthorization { age: 30 });
+
+        let decision = policy.decide(&cache, &mut pending, "a_service", &bad, None, 1030);
+        assert!(!decision.is_allowed());
+        assert_eq!(
+            decision.reason().to_string(),
+            "denied by a status cached 30s ago: application key is invalid"
+        );
+
+        // too old, or for another key or service
+        let decision = policy.decide(&cache, &mut pending, "a_service", &good, None, 1061);
+        assert_eq!(
+            decision.reason(),
+            &Reason::NoCachedStatus(FailureMode::Closed)
+        );
+        let other = Application::from_app_id_and_key("an_app", "other");
+        assert!(!policy
+            .decide(&cache, &mut pending, "a_service", &other, None, 1030)
+            .is_allowed());
+        assert!(!policy
+            .decide(&cache, &mut pending, "other_service", &good, None, 1030)
+            .is_allowed());
+
+        assert_eq!(cache.purge_older_than(60, 1061), 2);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn enforce_cached_limits_with_pending_usage() {
+        let policy = FailurePolicy::fail_open().cached_for(60);
+        let mut cache = AuthorizationCache::new();
+        let mut pending = PendingUsage::new();
+        let app = Application::from_app_id_and_key("an_app", "a_key");
+        cache.insert("a_service", &app, status(AUTHORIZED), 0);
+
+        let products = [("products", "2")];
+        let usage = Usage::new(&products);
+        let decide = |pending: &mut PendingUsage| {
+            policy.decide(&cache, pending, "a_service", &app, Some(&usage), 10)
+        };
+
+        // 5 hits used out of 10, and products count as hits
+        assert!(decide(&mut pending).is_allowed());
+        assert!(decide(&mut pending).is_allowed());
+        let decision = decide(&mut pending);
+        assert!(!decision.is_allowed());
+        assert_eq!(
+            decision.reason(),
+            &Reason::LimitsExceeded {
+                age: 10,
+                metric: "hits".into()
+            }
+        );
+
+        // denied usage is not recorded
+        assert_eq!(pending.hits("a_service", &app, "products"), 4);
+
+        let decision = policy.enforce_limits(false).decide(
+            &cache,
+            &mut pending,
+            "a_service",
+            &app,
+            Some(&usage),
+            10,
+        );
+        assert!(decision.is_allowed());
+    }
+
+    #[test]
+    fn drain_pending_usage() {
+        let mut pending = PendingUsage::new();
+        let with_key = Application::from_app_id_and_key("an_app", "a_key");
+        let other_key = Application::from_app_id_and_key("an_app", "other_key");
+        let hits = [("hits", "1"), ("products", "#0"), ("hits", "2")];
+
+        pending.record("a_service", &with_key, &Usage::new(&hits));
+        pending.record("a_service", &other_key, &Usage::new(&hits));
+        pending.record(
+            "a_service",
+            &Application::from_user_key("a_user_key"),
+            &Usage::new(&hits[..1]),
+        );
+
+        let reports = pending.drain();
+        assert!(pending.is_empty());
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].service_id(), "a_service");
+        assert_eq!(
+            reports[0].application(),
+            &Application::from_app_id("an_app")
+        );
+        assert_eq!(reports[0].usage(), [("hits".to_string(), "6".to_string())]);
+        assert_eq!(
+            reports[1].application(),
+            &Application::from_user_key("a_user_key")
+        );
+        assert_eq!(reports[1].usage(), [("hits".to_string(), "1".to_string())]);
+    }
+}
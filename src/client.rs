@@ -0,0 +1,283 @@
+// Clients sending API calls to a 3scale backend and parsing their responses.
+//
+// HTTP libraries are plugged in as transports, which only need to send a `Request` to a backend
+// and hand back the status and body of the response. `Client` and `BlockingClient` take care of
+// building the requests out of API calls and parsing what comes back, so that application code
+// does not depend on the HTTP library in use.
+use std::prelude::v1::*;
+
+use core::{future::Future, pin::Pin, str::FromStr};
+
+use crate::{
+    api_call::{ApiCall, Kind},
+    http::{Backend, Request},
+    response::Authorization,
+    ClientError, Error,
+};
+
+#[cfg(feature = "curl-easy")]
+mod curl;
+#[cfg(feature = "hyper")]
+mod hyper;
+#[cfg(any(feature = "reqwest-sync", feature = "reqwest-async"))]
+mod reqwest;
+#[cfg(feature = "ureq")]
+mod ureq;
+
+#[cfg(feature = "std")]
+pub mod breaker;
+#[cfg(feature = "std")]
+pub mod retry;
+
+/// A boxed future, as returned by asynchronous transports.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// The status code and body of a response, as received by a transport.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawResponse {
+    status: u16,
+    body: String,
+}
+
+impl RawResponse {
+    pub fn new<S: Into<String>>(status: u16, body: S) -> Self {
+        Self {
+            status,
+            body: body.into(),
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn body(&self) -> &str {
+        self.body.as_str()
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// The response to an API call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// Authorize and authrep calls, as well as rejected reports, are answered with an
+    /// `Authorization`. Note that it can be a denial or an error reported by 3scale.
+    Authorization(Authorization),
+    /// The report was accepted.
+    Accepted,
+}
+
+impl Response {
+    /// Parses the response to an API call of the given kind.
+    ///
+    /// Responses other than accepted reports are expected to carry an authorization whatever
+    /// their status code, since 3scale answers denials with client errors.
+    pub fn parse(kind: Kind, response: &RawResponse) -> Result<Self, Error> {
+        if kind.is_report() && response.is_success() {
+            return Ok(Response::Accepted);
+        }
+
+        Authorization::from_str(response.body())
+            .map(Response::Authorization)
+            .map_err(|e| match e {
+                Error::Xml(e) if !response.is_success() => {
+                    ClientError::unexpected_status(response.status(), e).into()
+                }
+                e => e,
+            })
+    }
+
+    pub fn authorization(&self) -> Option<&Authorization> {
+        match self {
+            Response::Authorization(authorization) => Some(authorization),
+            Response::Accepted => None,
+        }
+    }
+
+    pub fn into_authorization(self) -> Option<Authorization> {
+        match self {
+            Response::Authorization(authorization) => Some(authorization),
+            Response::Accepted => None,
+        }
+    }
+
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, Response::Accepted)
+    }
+}
+
+/// An asynchronous HTTP library able to send requests to a backend.
+pub trait Transport {
+    fn send<'a>(
+        &'a self,
+        request: Request,
+        backend: &'a Backend,
+    ) -> BoxFuture<'a, Result<RawResponse, Error>>;
+}
+
+/// A blocking HTTP library able to send requests to a backend.
+pub trait BlockingTransport {
+    fn send(&mut self, request: Request, backend: &Backend) -> Result<RawResponse, Error>;
+}
+
+/// Sends API calls to a backend through an asynchronous transport.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[cfg(feature = "reqwest-async")]
+/// # async fn run() -> Result<(), threescalers::Error> {
+/// use threescalers::{
+///     api_call::{ApiCall, Kind},
+///     application::Application,
+///     client::Client,
+///     credentials::Credentials,
+///     http::Backend,
+///     service::Service,
+///     transaction::Transaction,
+/// };
+///
+/// let client = Client::new(reqwest::Client::new(), Backend::default());
+///
+/// let service = Service::new("my_service_id", Credentials::from_token("my_token"));
+/// let app = Application::from_user_key("my_user_key");
+/// let txns = [Transaction::new(&app, None, None, None)];
+/// let mut builder = ApiCall::builder(&service);
+/// let apicall = builder.transactions(&txns).kind(Kind::Authorize).build()?;
+///
+/// let authorization = client.call(&apicall).await?.into_authorization();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Client<T> {
+    transport: T,
+    backend: Backend,
+}
+
+impl<T> Client<T> {
+    pub fn new(transport: T, backend: Backend) -> Self {
+        Self { transport, backend }
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn backend(&self) -> &Backend {
+        &self.backend
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+}
+
+impl<T: Transport> Client<T> {
+    pub async fn call(&self, apicall: &ApiCall<'_>) -> Result<Response, Error> {
+        let kind = apicall.kind();
+        let response = self.send(Request::from(apicall)).await?;
+
+        Response::parse(kind, &response)
+    }
+
+    /// Sends a request as is, ie. one built with a specific header policy.
+    pub async fn send(&self, request: Request) -> Result<RawResponse, Error> {
+        self.transport.send(request, &self.backend).await
+    }
+}
+
+/// Sends API calls to a backend through a blocking transport.
+#[derive(Debug, Clone)]
+pub struct BlockingClient<T> {
+    transport: T,
+    backend: Backend,
+}
+
+impl<T> BlockingClient<T> {
+    pub fn new(transport: T, backend: Backend) -> Self {
+        Self { transport, backend }
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn backend(&self) -> &Backend {
+        &self.backend
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+}
+
+impl<T: BlockingTransport> BlockingClient<T> {
+    pub fn call(&mut self, apicall: &ApiCall<'_>) -> Result<Response, Error> {
+        let kind = apicall.kind();
+        let response = self.send(Request::from(apicall))?;
+
+        Response::parse(kind, &response)
+    }
+
+    /// Sends a request as is, ie. one built with a specific header policy.
+    pub fn send(&mut self, request: Request) -> Result<RawResponse, Error> {
+        self.transport.send(request, &self.backend)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::fixtures::with_apicall;
+
+    struct Canned(Vec<(Request, RawResponse)>, RawResponse);
+
+    impl BlockingTransport for Canned {
+        fn send(&mut self, request: Request, _backend: &Backend) -> Result<RawResponse, Error> {
+            self.0.push((request, self.1.clone()));
+            Ok(self.1.clone())
+        }
+    }
+
+    fn call(kind: Kind, status: u16, body: &str) -> Result<Response, Error> {
+        let mut client = BlockingClient::new(
+            Canned(Vec::new(), RawResponse::new(status, body)),
+            Backend::default(),
+        );
+        let response = with_apicall(kind, |apicall| client.call(apicall));
+        assert_eq!(client.transport().0.len(), 1);
+
+        response
+    }
+
+    #[test]
+    fn parse_responses() {
+        let status =
+            "<status><authorized>false</authorized><reason>no</reason><plan>P</plan></status>";
+        let error = r#"<error code="provider_key_invalid">invalid</error>"#;
+
+        assert!(call(Kind::Report, 202, "").unwrap().is_accepted());
+        assert!(call(Kind::Report, 403, error)
+            .unwrap()
+            .into_authorization()
+            .unwrap()
+            .is_error());
+        assert!(call(Kind::Report, 500, "").is_err());
+
+        let authorization = call(Kind::AuthRep, 409, status).unwrap();
+        assert!(authorization.authorization().unwrap().is_status());
+        match call(Kind::Authorize, 502, "<html></html>") {
+            Err(Error::Client(e)) => {
+                assert_eq!(e.kind(), crate::ClientErrorKind::Response);
+                assert!(e
+                    .to_string()
+                    .starts_with("unexpected response with status 502"));
+            }
+            r => core::panic!("unexpected result {:?}", r),
+        }
+    }
+}
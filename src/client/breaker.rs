@@ -0,0 +1,597 @@
+// A circuit breaker stopping calls to a backend that keeps failing or answering slowly, so that
+// callers fail fast rather than wait on timeouts while the backend recovers.
+use std::prelude::v1::*;
+
+use core::{fmt, time::Duration};
+use std::{collections::VecDeque, sync::Mutex, time::Instant};
+
+use super::{BlockingTransport, BoxFuture, RawResponse, Transport};
+use crate::{
+    http::{Backend, Request},
+    Error,
+};
+
+/// The state of a circuit breaker.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum State {
+    /// Calls go through, and their outcomes are tracked.
+    Closed,
+    /// Calls are rejected right away.
+    Open,
+    /// A limited number of trial calls go through to find out whether the backend recovered.
+    HalfOpen,
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            State::Closed => "closed",
+            State::Open => "open",
+            State::HalfOpen => "half-open",
+        })
+    }
+}
+
+/// Thresholds opening a circuit breaker, and how it recovers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BreakerPolicy {
+    window: usize,
+    min_calls: usize,
+    failure_rate: u8,
+    slow_call: Option<Duration>,
+    slow_rate: u8,
+    open_for: Duration,
+    trial_calls: u32,
+}
+
+impl Default for BreakerPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BreakerPolicy {
+    /// Opens when at least half of the last 20 calls failed, given at least 10 calls, and tries
+    /// again with a single call after 5 seconds.
+    pub fn new() -> Self {
+        Self {
+            window: 20,
+            min_calls: 10,
+            failure_rate: 50,
+            slow_call: None,
+            slow_rate: 100,
+            open_for: Duration::from_secs(5),
+            trial_calls: 1,
+        }
+    }
+
+    /// The number of most recent calls whose outcomes are considered, and how many of them are
+    /// needed before opening.
+    pub fn window(mut self, window: usize, min_calls: usize) -> Self {
+        self.window = window.max(1);
+        self.min_calls = min_calls.clamp(1, self.window);
+        self
+    }
+
+    /// The percentage of failed calls opening the breaker. Failures are transport errors and
+    /// server errors.
+    pub fn failure_rate(mut self, percent: u8) -> Self {
+        self.failure_rate = percent.min(100);
+        self
+    }
+
+    /// Opens the breaker when the given percentage of calls take at least `threshold`.
+    pub fn slow_calls(mut self, threshold: Duration, percent: u8) -> Self {
+        self.slow_call = Some(threshold);
+        self.slow_rate = percent.min(100);
+        self
+    }
+
+    /// How long the breaker stays open before letting trial calls through.
+    pub fn open_for(mut self, duration: Duration) -> Self {
+        self.open_for = duration;
+        self
+    }
+
+    /// The number of successful trial calls needed to close the breaker again.
+    pub fn trial_calls(mut self, calls: u32) -> Self {
+        self.trial_calls = calls.max(1);
+        self
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Outcome {
+    failed: bool,
+    slow: bool,
+}
+
+type Listener = Box<dyn Fn(State, State) + Send + Sync>;
+
+/// The state machine of a circuit breaker, fed with the outcomes of calls.
+///
+/// Time is passed in by the caller, which makes it usable with any transport.
+pub struct Breaker {
+    policy: BreakerPolicy,
+    state: State,
+    outcomes: VecDeque<Outcome>,
+    opened_at: Option<Instant>,
+    trials: u32,
+    successes: u32,
+    listener: Option<Listener>,
+}
+
+impl fmt::Debug for Breaker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Breaker")
+            .field("policy", &self.policy)
+            .field("state", &self.state)
+            .field("outcomes", &self.outcomes)
+            .field("opened_at", &self.opened_at)
+            .field("trials", &self.trials)
+            .field("successes", &self.successes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Breaker {
+    pub fn new(policy: BreakerPolicy) -> Self {
+        Self {
+            policy,
+            state: State::Closed,
+            outcomes: VecDeque::with_capacity(policy.window),
+            opened_at: None,
+            trials: 0,
+            successes: 0,
+            listener: None,
+        }
+    }
+
+    /// Calls `listener` with the previous and the new state whenever the state changes.
+    ///
+    /// The listener is called while the breaker is being updated, so it should not block.
+    pub fn on_state_change<F: Fn(State, State) + Send + Sync + 'static>(
+        mut self,
+        listener: F,
+    ) -> Self {
+        self.listener = Some(Box::new(listener));
+        self
+    }
+
+    pub fn policy(&self) -> &BreakerPolicy {
+        &self.policy
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Whether a call can be made now. Every allowed call must have its outcome recorded, or be
+    /// cancelled.
+    pub fn allow(&mut self, now: Instant) -> bool {
+        if self.state == State::Open {
+            match self.opened_at {
+                Some(opened_at) if now.duration_since(opened_at) >= self.policy.open_for => {
+                    self.trials = 0;
+                    self.successes = 0;
+                    self.transition(State::HalfOpen);
+                }
+                _ => return false,
+            }
+        }
+
+        match self.state {
+            State::HalfOpen if self.trials >= self.policy.trial_calls => false,
+            State::HalfOpen => {
+                self.trials += 1;
+                true
+            }
+            _ => true,
+        }
+    }
+
+    /// Gives back an allowed call that says nothing about the backend, ie. one that could not
+    /// be sent because the request is invalid.
+    pub fn cancel(&mut self) {
+        if self.state == State::HalfOpen {
+            self.trials = self.trials.saturating_sub(1);
+        }
+    }
+
+    /// Records the outcome of an allowed call.
+    pub fn record(&mut self, failed: bool, latency: Duration, now: Instant) {
+        let slow = self
+            .policy
+            .slow_call
+            .map_or(false, |threshold| latency >= threshold);
+
+        match self.state {
+            State::Closed => {
+                if self.outcomes.len() == self.policy.window {
+                    self.outcomes.pop_front();
+                }
+                self.outcomes.push_back(Outcome { failed, slow });
+
+                if self.should_open() {
+                    self.open(now);
+                }
+            }
+            State::HalfOpen if failed || slow => self.open(now),
+            State::HalfOpen => {
+                self.successes += 1;
+                if self.successes >= self.policy.trial_calls {
+                    self.outcomes.clear();
+                    self.transition(State::Closed);
+                }
+            }
+            // calls started before opening
+            State::Open => (),
+        }
+    }
+
+    fn should_open(&self) -> bool {
+        let calls = self.outcomes.len();
+        if calls < self.policy.min_calls {
+            return false;
+        }
+
+        let exceeds = |count: usize, percent: u8| count * 100 >= calls * usize::from(percent);
+        let failed = self.outcomes.iter().filter(|o| o.failed).count();
+        let slow = self.outcomes.iter().filter(|o| o.slow).count();
+
+        (failed > 0 && exceeds(failed, self.policy.failure_rate))
+            || (slow > 0 && exceeds(slow, self.policy.slow_rate))
+    }
+
+    fn open(&mut self, now: Instant) {
+        self.opened_at = Some(now);
+        self.outcomes.clear();
+        self.transition(State::Open);
+    }
+
+    fn transition(&mut self, state: State) {
+        let previous = core::mem::replace(&mut self.state, state);
+        if let Some(listener) = &self.listener {
+            listener(previous, state);
+        }
+    }
+}
+
+/// A transport stopping calls to a failing backend.
+///
+/// Calls rejected by an open breaker fail right away with an error, without reaching the
+/// backend.
+#[derive(Debug)]
+pub struct CircuitBreaker<T> {
+    transport: T,
+    breaker: Mutex<Breaker>,
+}
+
+impl<T> CircuitBreaker<T> {
+    pub fn new(transport: T, breaker: Breaker) -> Self {
+        Self {
+            transport,
+            breaker: Mutex::new(breaker),
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.lock().state()
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Breaker> {
+        lock(&self.breaker)
+    }
+}
+
+fn lock(breaker: &Mutex<Breaker>) -> std::sync::MutexGuard<'_, Breaker> {
+    // the breaker is always left consistent, so a panicking listener can be ignored
+    breaker
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// An allowed call, which records a failure unless released with an outcome. Calls can end without
+// one when their future is dropped, ie. on a timeout, or when the transport panics, and they would
+// otherwise hold on to their trial forever while half-open.
+struct Permit<'a> {
+    breaker: &'a Mutex<Breaker>,
+    start: Instant,
+    released: bool,
+}
+
+impl<'a> Permit<'a> {
+    fn acquire(breaker: &'a Mutex<Breaker>) -> Result<Self, Error> {
+        let start = Instant::now();
+
+        if lock(breaker).allow(start) {
+            Ok(Self {
+                breaker,
+                start,
+                released: false,
+            })
+        } else {
+            Err(Error::CircuitOpen)
+        }
+    }
+
+    fn release(mut self, outcome: &Result<RawResponse, Error>) {
+        self.released = true;
+
+        let failed = match outcome {
+            Ok(response) => response.status() >= 500,
+            Err(e) if e.is_transport() => true,
+            // errors on our side, like invalid requests, must not open the breaker
+            Err(_) => return lock(self.breaker).cancel(),
+        };
+        self.record(failed);
+    }
+
+    fn record(&self, failed: bool) {
+        let now = Instant::now();
+
+        lock(self.breaker).record(failed, now.duration_since(self.start), now);
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if !self.released {
+            self.record(true);
+        }
+    }
+}
+
+impl<T: BlockingTransport> BlockingTransport for CircuitBreaker<T> {
+    fn send(&mut self, request: Request, backend: &Backend) -> Result<RawResponse, Error> {
+        let permit = Permit::acquire(&self.breaker)?;
+        let outcome = self.transport.send(request, backend);
+        permit.release(&outcome);
+
+        outcome
+    }
+}
+
+impl<T: Transport + Sync> Transport for CircuitBreaker<T> {
+    fn send<'a>(
+        &'a self,
+        request: Request,
+        backend: &'a Backend,
+    ) -> BoxFuture<'a, Result<RawResponse, Error>> {
+        Box::pin(async move {
+            let permit = Permit::acquire(&self.breaker)?;
+            let outcome = self.transport.send(request, backend).await;
+            permit.release(&outcome);
+
+            outcome
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api_call::Kind, http::HeaderError, util::fixtures, ClientError, ClientErrorKind};
+    use std::sync::Arc;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn open_on_failure_rate() {
+        let mut breaker = Breaker::new(BreakerPolicy::new().window(4, 4).failure_rate(50));
+        let now = Instant::now();
+
+        for failed in [true, false, false] {
+            assert!(breaker.allow(now));
+            breaker.record(failed, ms(1), now);
+        }
+        assert_eq!(breaker.state(), State::Closed);
+
+        breaker.record(false, ms(1), now);
+        breaker.record(true, ms(1), now);
+        assert_eq!(breaker.state(), State::Closed);
+        breaker.record(true, ms(1), now);
+        assert_eq!(breaker.state(), State::Open);
+        assert!(!breaker.allow(now));
+    }
+
+    #[test]
+    fn open_on_slow_calls() {
+        let policy = BreakerPolicy::new().window(2, 2).slow_calls(ms(100), 100);
+        let mut breaker = Breaker::new(policy);
+        let now = Instant::now();
+
+        breaker.record(false, ms(100), now);
+        breaker.record(false, ms(99), now);
+        assert_eq!(breaker.state(), State::Closed);
+        breaker.record(false, ms(150), now);
+        assert_eq!(breaker.state(), State::Closed);
+        breaker.record(false, ms(150), now);
+        assert_eq!(breaker.state(), State::Open);
+    }
+
+    #[test]
+    fn recover_through_half_open() {
+        let transitions = Arc::new(Mutex::new(Vec::new()));
+        let record = transitions.clone();
+        let policy = BreakerPolicy::new()
+            .window(1, 1)
+            .open_for(Duration::from_secs(5))
+            .trial_calls(2);
+        let mut breaker = Breaker::new(policy)
+            .on_state_change(move |from, to| record.lock().unwrap().push((from, to)));
+        let start = Instant::now();
+
+        breaker.record(true, ms(1), start);
+        assert!(!breaker.allow(start + Duration::from_secs(4)));
+
+        // a failed trial opens the breaker again
+        let later = start + Duration::from_secs(5);
+        assert!(breaker.allow(later));
+        breaker.record(true, ms(1), later);
+        assert!(!breaker.allow(later));
+
+        let later = later + Duration::from_secs(5);
+        assert!(breaker.allow(later));
+        assert!(breaker.allow(later));
+        assert!(!breaker.allow(later));
+        breaker.record(false, ms(1), later);
+        assert_eq!(breaker.state(), State::HalfOpen);
+        breaker.record(false, ms(1), later);
+        assert_eq!(breaker.state(), State::Closed);
+
+        use State::*;
+        assert_eq!(
+            *transitions.lock().unwrap(),
+            vec![
+                (Closed, Open),
+                (Open, HalfOpen),
+                (HalfOpen, Open),
+                (Open, HalfOpen),
+                (HalfOpen, Closed)
+            ]
+        );
+    }
+
+    struct Failing(u32);
+
+    impl BlockingTransport for Failing {
+        fn send(&mut self, _request: Request, _backend: &Backend) -> Result<RawResponse, Error> {
+            self.0 += 1;
+            Ok(RawResponse::new(503, ""))
+        }
+    }
+
+    impl Transport for Failing {
+        fn send<'a>(
+            &'a self,
+            _request: Request,
+            _backend: &'a Backend,
+        ) -> BoxFuture<'a, Result<RawResponse, Error>> {
+            Box::pin(core::future::ready(Err(ClientError::new(
+                ClientErrorKind::Connect,
+                "connection refused",
+            )
+            .into())))
+        }
+    }
+
+    fn request() -> Request {
+        fixtures::request(Kind::Authorize)
+    }
+
+    #[test]
+    fn short_circuit_blocking_calls() {
+        let breaker = Breaker::new(BreakerPolicy::new().window(2, 2));
+        let mut transport = CircuitBreaker::new(Failing(0), breaker);
+
+        for call in 0..4 {
+            let outcome = BlockingTransport::send(&mut transport, request(), &Backend::default());
+            assert_eq!(outcome.is_ok(), call < 2);
+        }
+        assert_eq!(transport.transport().0, 2);
+        assert_eq!(transport.state(), State::Open);
+    }
+
+    // Fails to set up every request, without reaching the backend.
+    struct Invalid;
+
+    impl BlockingTransport for Invalid {
+        fn send(&mut self, _request: Request, _backend: &Backend) -> Result<RawResponse, Error> {
+            Err(HeaderError::InvalidName("X Bad".into()).into())
+        }
+    }
+
+    #[test]
+    fn ignore_local_errors() {
+        let breaker = Breaker::new(BreakerPolicy::new().window(1, 1).trial_calls(1));
+        let mut transport = CircuitBreaker::new(Invalid, breaker);
+
+        for _ in 0..3 {
+            let outcome = BlockingTransport::send(&mut transport, request(), &Backend::default());
+            assert!(matches!(outcome, Err(Error::InvalidHeader(_))));
+        }
+        assert_eq!(transport.state(), State::Closed);
+
+        // the trial of a cancelled call can be used again
+        let mut breaker = Breaker::new(BreakerPolicy::new().window(1, 1).open_for(ms(0)));
+        let now = Instant::now();
+        breaker.record(true, ms(1), now);
+        assert!(breaker.allow(now));
+        assert!(!breaker.allow(now));
+        breaker.cancel();
+        assert!(breaker.allow(now));
+        assert_eq!(breaker.state(), State::HalfOpen);
+    }
+
+    // Never answers while hanging, like a backend that stopped responding.
+    struct Hanging(std::sync::atomic::AtomicBool);
+
+    impl Transport for Hanging {
+        fn send<'a>(
+            &'a self,
+            _request: Request,
+            _backend: &'a Backend,
+        ) -> BoxFuture<'a, Result<RawResponse, Error>> {
+            if self.0.load(std::sync::atomic::Ordering::SeqCst) {
+                Box::pin(core::future::pending())
+            } else {
+                Box::pin(core::future::ready(Ok(RawResponse::new(200, ""))))
+            }
+        }
+    }
+
+    struct Noop;
+
+    impl std::task::Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn dropped_trials_count_as_failures() {
+        let mut breaker = Breaker::new(BreakerPolicy::new().window(1, 1).open_for(ms(0)));
+        let now = Instant::now();
+        breaker.record(true, ms(1), now);
+        let transport = CircuitBreaker::new(Hanging(true.into()), breaker);
+
+        // the trial is dropped after starting, as a timeout would do
+        let waker = std::task::Waker::from(Arc::new(Noop));
+        let backend = Backend::default();
+        let mut call = Transport::send(&transport, request(), &backend);
+        assert!(call
+            .as_mut()
+            .poll(&mut std::task::Context::from_waker(&waker))
+            .is_pending());
+        drop(call);
+        assert_eq!(transport.state(), State::Open);
+
+        transport
+            .transport()
+            .0
+            .store(false, std::sync::atomic::Ordering::SeqCst);
+        let outcome = Transport::send(&transport, request(), &Backend::default()).await;
+        assert_eq!(outcome.unwrap().status(), 200);
+        assert_eq!(transport.state(), State::Closed);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn short_circuit_async_calls() {
+        let transport =
+            CircuitBreaker::new(Failing(0), Breaker::new(BreakerPolicy::new().window(1, 1)));
+
+        let outcome = Transport::send(&transport, request(), &Backend::default()).await;
+        assert_eq!(outcome.unwrap_err().to_string(), "connection refused");
+        let outcome = Transport::send(&transport, request(), &Backend::default()).await;
+        assert!(matches!(outcome, Err(Error::CircuitOpen)));
+    }
+}
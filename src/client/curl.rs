@@ -0,0 +1,80 @@
+use std::prelude::v1::*;
+
+use curl::easy::{Easy, Transfer};
+
+use super::{BlockingTransport, RawResponse};
+use crate::{
+    http::{
+        request::{curl::setopt_error, SetupRequest},
+        Backend, Request,
+    },
+    ClientError, ClientErrorKind, Error,
+};
+
+fn perform<'data>(
+    mut transfer: Transfer<'_, 'data>,
+    body: &'data mut Vec<u8>,
+) -> Result<(), Error> {
+    transfer
+        .write_function(|data| {
+            body.extend_from_slice(data);
+            Ok(data.len())
+        })
+        .map_err(|e| setopt_error(e, "failed to set curl client write function"))?;
+    transfer.perform().map_err(|e| {
+        let kind = if e.is_operation_timedout() {
+            ClientErrorKind::Timeout
+        } else if e.is_couldnt_connect()
+            || e.is_couldnt_resolve_host()
+            || e.is_couldnt_resolve_proxy()
+        {
+            ClientErrorKind::Connect
+        } else if e.is_url_malformed() || e.is_unsupported_protocol() {
+            ClientErrorKind::Request
+        } else {
+            ClientErrorKind::Transport
+        };
+
+        ClientError::new(kind, "failed to send request")
+            .with_source(e)
+            .into()
+    })
+}
+
+impl BlockingTransport for Easy {
+    fn send(&mut self, request: Request, backend: &Backend) -> Result<RawResponse, Error> {
+        let mut body = Vec::new();
+
+        // requests with a body come with a transfer already reading it, otherwise the easy
+        // handle is only configured and a new transfer is started once it is released
+        if request.parameters.body().is_some() {
+            let transfer = self
+                .setup_request(request, backend)?
+                .into_transfer()
+                .ok_or_else(|| {
+                    ClientError::new(
+                        ClientErrorKind::Request,
+                        "curl client did not set up a transfer for the body",
+                    )
+                })?;
+            perform(transfer, &mut body)?;
+        } else {
+            let _ = self.setup_request(request, backend)?;
+            perform(self.transfer(), &mut body)?;
+        }
+
+        let status = self.response_code().map_err(|e| {
+            ClientError::new(ClientErrorKind::Response, "failed to get response status")
+                .with_source(e)
+        })?;
+        let body = String::from_utf8(body).map_err(|e| {
+            ClientError::new(
+                ClientErrorKind::Response,
+                "response body is not valid UTF-8",
+            )
+            .with_source(e)
+        })?;
+
+        Ok(RawResponse::new(status as u16, body))
+    }
+}
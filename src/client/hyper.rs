@@ -0,0 +1,38 @@
+use std::prelude::v1::*;
+
+use hyper_util::client::legacy::{connect::Connect, Client};
+
+use super::{BoxFuture, RawResponse, Transport};
+use crate::{
+    http::{
+        request::hyper::{build_request, send, Body},
+        Backend, Request,
+    },
+    ClientError, ClientErrorKind, Error,
+};
+
+impl<C> Transport for Client<C, Body>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    fn send<'a>(
+        &'a self,
+        request: Request,
+        backend: &'a Backend,
+    ) -> BoxFuture<'a, Result<RawResponse, Error>> {
+        Box::pin(async move {
+            let request = build_request(request, backend)?;
+            let response = send(self, request).await?;
+            let status = response.status().as_u16();
+            let body = String::from_utf8(response.into_body().to_vec()).map_err(|e| {
+                ClientError::new(
+                    ClientErrorKind::Response,
+                    "response body is not valid UTF-8",
+                )
+                .with_source(e)
+            })?;
+
+            Ok(RawResponse::new(status, body))
+        })
+    }
+}
@@ -0,0 +1,62 @@
+use std::prelude::v1::*;
+
+use super::RawResponse;
+use crate::{
+    http::{request::SetupRequest, Backend, Request},
+    ClientError, ClientErrorKind, Error,
+};
+
+fn client_error(e: reqwest::Error, message: &str) -> ClientError {
+    let kind = if e.is_timeout() {
+        ClientErrorKind::Timeout
+    } else if e.is_connect() {
+        ClientErrorKind::Connect
+    } else if e.is_builder() {
+        ClientErrorKind::Request
+    } else {
+        ClientErrorKind::Transport
+    };
+
+    ClientError::new(kind, message).with_source(e)
+}
+
+#[cfg(feature = "reqwest-async")]
+impl super::Transport for reqwest::Client {
+    fn send<'a>(
+        &'a self,
+        request: Request,
+        backend: &'a Backend,
+    ) -> super::BoxFuture<'a, Result<RawResponse, Error>> {
+        Box::pin(async move {
+            let response = self
+                .clone()
+                .setup_request(request, backend)?
+                .send()
+                .await
+                .map_err(|e| client_error(e, "failed to send request"))?;
+            let status = response.status().as_u16();
+            let body = response
+                .text()
+                .await
+                .map_err(|e| client_error(e, "failed to read response body"))?;
+
+            Ok(RawResponse::new(status, body))
+        })
+    }
+}
+
+#[cfg(feature = "reqwest-sync")]
+impl super::BlockingTransport for reqwest::blocking::Client {
+    fn send(&mut self, request: Request, backend: &Backend) -> Result<RawResponse, Error> {
+        let response = self
+            .setup_request(request, backend)?
+            .send()
+            .map_err(|e| client_error(e, "failed to send request"))?;
+        let status = response.status().as_u16();
+        let body = response
+            .text()
+            .map_err(|e| client_error(e, "failed to read response body"))?;
+
+        Ok(RawResponse::new(status, body))
+    }
+}
@@ -0,0 +1,524 @@
+// Retries of failed calls to 3scale.
+//
+// Only calls that are safe to repeat are retried by default. Authrep and report calls increment
+// usage counters, and a call that failed from our side of the connection could still have been
+// processed by 3scale, so retrying them risks counting usage twice.
+use std::prelude::v1::*;
+
+use core::{
+    future::Future,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use std::time::Instant;
+
+use super::{BlockingTransport, BoxFuture, RawResponse, Transport};
+use crate::{
+    api_call::Kind,
+    http::{endpoints::*, Backend, Request},
+    Error,
+};
+
+/// Exponential backoff between attempts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    factor: u32,
+    jitter: bool,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(50), Duration::from_secs(1))
+    }
+}
+
+impl Backoff {
+    /// Creates a backoff doubling the delay from `initial` up to `max`, with full jitter.
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            factor: 2,
+            jitter: true,
+        }
+    }
+
+    /// A backoff retrying right away.
+    pub fn none() -> Self {
+        Self::new(Duration::ZERO, Duration::ZERO)
+    }
+
+    pub fn factor(mut self, factor: u32) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    /// Whether to pick a random delay between zero and the computed one, so that clients failing
+    /// at the same time do not retry at the same time.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// The delay before the given retry, starting from 0, using `random` to add jitter.
+    pub fn delay(&self, retry: u32, random: u64) -> Duration {
+        let delay = self
+            .factor
+            .checked_pow(retry)
+            .and_then(|factor| self.initial.checked_mul(factor))
+            .map_or(self.max, |delay| delay.min(self.max));
+
+        if self.jitter {
+            // scale by a random fraction with 16 bits of precision
+            delay.mul_f64((random >> 48) as f64 / f64::from(1u32 << 16))
+        } else {
+            delay
+        }
+    }
+}
+
+/// Which failed calls to retry, how many times, and how long to wait in between.
+///
+/// # Examples
+///
+/// ```
+/// use core::time::Duration;
+/// use threescalers::{
+///     api_call::Kind,
+///     client::retry::{Backoff, RetryPolicy},
+/// };
+///
+/// let policy = RetryPolicy::new()
+///     .max_retries(5)
+///     .backoff(Backoff::new(Duration::from_millis(10), Duration::from_millis(500)))
+///     .deadline(Duration::from_secs(2));
+///
+/// assert!(policy.retries(Kind::Authorize));
+/// assert!(!policy.retries(Kind::Report));
+/// assert!(policy.retry_kind(Kind::Report).retries(Kind::Report));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    backoff: Backoff,
+    deadline: Option<Duration>,
+    kinds: Vec<Kind>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RetryPolicy {
+    /// Retries authorize calls up to 3 times with the default backoff.
+    pub fn new() -> Self {
+        Self {
+            max_retries: 3,
+            backoff: Backoff::default(),
+            deadline: None,
+            kinds: vec![Kind::Authorize],
+        }
+    }
+
+    /// Never retries.
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            backoff: Backoff::none(),
+            deadline: None,
+            kinds: Vec::new(),
+        }
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Stops retrying once the next attempt would start after this time since the first one.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Also retries calls of the given kind. Beware that retrying authrep and report calls can
+    /// count usage twice.
+    pub fn retry_kind(mut self, kind: Kind) -> Self {
+        if !self.kinds.contains(&kind) {
+            self.kinds.push(kind);
+        }
+        self
+    }
+
+    /// Whether calls of the given kind are retried.
+    pub fn retries(&self, kind: Kind) -> bool {
+        self.max_retries > 0 && self.kinds.contains(&kind)
+    }
+
+    /// Whether the outcome of an attempt is worth retrying: transport errors and server errors
+    /// that are likely to be transient. Errors setting up the request, or an open circuit
+    /// breaker, would fail again in the same way.
+    pub fn is_transient(outcome: &Result<RawResponse, Error>) -> bool {
+        match outcome {
+            Ok(response) => matches!(response.status(), 408 | 429 | 500 | 502 | 503 | 504),
+            Err(e) => e.is_transport(),
+        }
+    }
+
+    // Returns how long to wait before retrying, if the call should be retried.
+    fn next_delay(
+        &self,
+        kind: Option<Kind>,
+        retry: u32,
+        elapsed: Duration,
+        random: u64,
+        outcome: &Result<RawResponse, Error>,
+    ) -> Option<Duration> {
+        if retry >= self.max_retries
+            || !kind.map_or(false, |kind| self.kinds.contains(&kind))
+            || !Self::is_transient(outcome)
+        {
+            return None;
+        }
+
+        let delay = self.backoff.delay(retry, random);
+        match self.deadline {
+            Some(deadline) if elapsed + delay > deadline => None,
+            _ => Some(delay),
+        }
+    }
+}
+
+// Requests only carry their endpoint, which tells the kind of call apart.
+fn kind_of(request: &Request) -> Option<Kind> {
+    match (request.method, request.path) {
+        AUTHORIZE_ENDPOINT | OAUTH_AUTHORIZE_ENDPOINT => Some(Kind::Authorize),
+        AUTHREP_ENDPOINT | OAUTH_AUTHREP_ENDPOINT => Some(Kind::AuthRep),
+        REPORT_ENDPOINT => Some(Kind::Report),
+        _ => None,
+    }
+}
+
+/// Sleeps asynchronously, as required to retry with asynchronous transports.
+///
+/// This is implemented for functions like `tokio::time::sleep`.
+pub trait Sleep {
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+impl<F, Fut> Sleep for F
+where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(self(duration))
+    }
+}
+
+/// A transport retrying failed calls according to a `RetryPolicy`.
+///
+/// Blocking transports sleep the current thread in between attempts, whereas asynchronous ones
+/// need a `Sleep` implementation set with `with_sleep`.
+#[derive(Debug)]
+pub struct Retry<T, S = ()> {
+    transport: T,
+    policy: RetryPolicy,
+    sleep: S,
+    state: AtomicU64,
+}
+
+impl<T> Retry<T> {
+    pub fn new(transport: T, policy: RetryPolicy) -> Self {
+        use core::hash::{BuildHasher, Hasher};
+
+        // seed the jitter with the process' random hashing keys
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_usize(&policy as *const RetryPolicy as usize);
+
+        Self {
+            transport,
+            policy,
+            sleep: (),
+            state: AtomicU64::new(hasher.finish() | 1),
+        }
+    }
+}
+
+impl<T, S> Retry<T, S> {
+    pub fn with_sleep<S2: Sleep>(self, sleep: S2) -> Retry<T, S2> {
+        Retry {
+            transport: self.transport,
+            policy: self.policy,
+            sleep,
+            state: self.state,
+        }
+    }
+
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    // xorshift64*, which is plenty for jitter
+    fn random(&self) -> u64 {
+        let mut x = self.state.load(Ordering::Relaxed);
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state.store(x, Ordering::Relaxed);
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+impl<T: BlockingTransport, S> BlockingTransport for Retry<T, S> {
+    fn send(&mut self, request: Request, backend: &Backend) -> Result<RawResponse, Error> {
+        let kind = kind_of(&request);
+        let start = Instant::now();
+        let mut retry = 0;
+
+        loop {
+            let outcome = self.transport.send(request.clone(), backend);
+            let random = self.random();
+            match self
+                .policy
+                .next_delay(kind, retry, start.elapsed(), random, &outcome)
+            {
+                Some(delay) => std::thread::sleep(delay),
+                None => return outcome,
+            }
+            retry += 1;
+        }
+    }
+}
+
+impl<T, S> Transport for Retry<T, S>
+where
+    T: Transport + Sync,
+    S: Sleep + Sync,
+{
+    fn send<'a>(
+        &'a self,
+        request: Request,
+        backend: &'a Backend,
+    ) -> BoxFuture<'a, Result<RawResponse, Error>> {
+        Box::pin(async move {
+            let kind = kind_of(&request);
+            let start = Instant::now();
+            let mut retry = 0;
+
+            loop {
+                let outcome = self.transport.send(request.clone(), backend).await;
+                match self
+                    .policy
+                    .next_delay(kind, retry, start.elapsed(), self.random(), &outcome)
+                {
+                    Some(delay) => self.sleep.sleep(delay).await,
+                    None => return outcome,
+                }
+                retry += 1;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http::HeaderError, util::fixtures::request, ClientError, ClientErrorKind};
+    use std::sync::Mutex;
+
+    // Fails with the given outcomes before succeeding, counting attempts.
+    struct Flaky {
+        failures: Mutex<Vec<Option<u16>>>,
+        attempts: Mutex<u32>,
+    }
+
+    impl Flaky {
+        fn new(failures: &[Option<u16>]) -> Self {
+            Self {
+                failures: Mutex::new(failures.iter().rev().copied().collect()),
+                attempts: Mutex::new(0),
+            }
+        }
+
+        fn attempt(&self) -> Result<RawResponse, Error> {
+            *self.attempts.lock().unwrap() += 1;
+            match self.failures.lock().unwrap().pop() {
+                Some(Some(status)) => Ok(RawResponse::new(status, "")),
+                Some(None) => {
+                    Err(ClientError::new(ClientErrorKind::Transport, "connection reset").into())
+                }
+                None => Ok(RawResponse::new(200, "")),
+            }
+        }
+
+        fn attempts(&self) -> u32 {
+            *self.attempts.lock().unwrap()
+        }
+    }
+
+    impl BlockingTransport for Flaky {
+        fn send(&mut self, _request: Request, _backend: &Backend) -> Result<RawResponse, Error> {
+            self.attempt()
+        }
+    }
+
+    impl Transport for Flaky {
+        fn send<'a>(
+            &'a self,
+            _request: Request,
+            _backend: &'a Backend,
+        ) -> BoxFuture<'a, Result<RawResponse, Error>> {
+            Box::pin(core::future::ready(self.attempt()))
+        }
+    }
+
+    // Rejects every call like an open circuit breaker, counting them.
+    #[derive(Default)]
+    struct Open(u32);
+
+    impl BlockingTransport for Open {
+        fn send(&mut self, _request: Request, _backend: &Backend) -> Result<RawResponse, Error> {
+            self.0 += 1;
+            Err(Error::CircuitOpen)
+        }
+    }
+
+    #[test]
+    fn backoff_delays() {
+        let backoff =
+            Backoff::new(Duration::from_millis(10), Duration::from_millis(50)).jitter(false);
+        let delays = (0..5).map(|n| backoff.delay(n, 0)).collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            [10, 20, 40, 50, 50].map(Duration::from_millis).to_vec()
+        );
+        assert_eq!(backoff.delay(u32::MAX, 0), Duration::from_millis(50));
+
+        let backoff = backoff.jitter(true);
+        assert_eq!(backoff.delay(2, 0), Duration::ZERO);
+        assert!(backoff.delay(2, u64::MAX) < Duration::from_millis(40));
+        assert!(backoff.delay(2, u64::MAX) > Duration::from_millis(39));
+    }
+
+    #[test]
+    fn retry_only_idempotent_calls() {
+        let policy = RetryPolicy::new().backoff(Backoff::none());
+        let failures = [None, Some(503), Some(502)];
+
+        let mut retry = Retry::new(Flaky::new(&failures), policy.clone());
+        let outcome =
+            BlockingTransport::send(&mut retry, request(Kind::Authorize), &Backend::default());
+        assert_eq!(outcome.unwrap().status(), 200);
+        assert_eq!(retry.transport().attempts(), 4);
+
+        for kind in [Kind::AuthRep, Kind::Report] {
+            let mut retry = Retry::new(Flaky::new(&failures), policy.clone());
+            let outcome = BlockingTransport::send(&mut retry, request(kind), &Backend::default());
+            assert!(outcome.is_err());
+            assert_eq!(retry.transport().attempts(), 1);
+        }
+
+        // client errors are not transient, and retries are limited
+        let mut retry = Retry::new(Flaky::new(&[Some(403)]), policy.clone());
+        let outcome =
+            BlockingTransport::send(&mut retry, request(Kind::Authorize), &Backend::default());
+        assert_eq!(outcome.unwrap().status(), 403);
+        assert_eq!(retry.transport().attempts(), 1);
+
+        let mut retry = Retry::new(Flaky::new(&[None; 5]), policy.retry_kind(Kind::Report));
+        let outcome =
+            BlockingTransport::send(&mut retry, request(Kind::Report), &Backend::default());
+        assert!(outcome.is_err());
+        assert_eq!(retry.transport().attempts(), 4);
+    }
+
+    #[test]
+    fn retry_only_transport_errors() {
+        let transient = |e: Error| RetryPolicy::is_transient(&Err(e));
+
+        assert!(transient(
+            ClientError::new(ClientErrorKind::Timeout, "timed out").into()
+        ));
+        assert!(transient(
+            ClientError::new(ClientErrorKind::Connect, "connection refused").into()
+        ));
+        assert!(!transient(
+            ClientError::new(ClientErrorKind::Request, "invalid URI").into()
+        ));
+        assert!(!transient(HeaderError::InvalidName("a b".into()).into()));
+        assert!(!transient(Error::InvalidMethod("TRACE".into())));
+        assert!(!transient(Error::CircuitOpen));
+
+        let policy = RetryPolicy::new().backoff(Backoff::none());
+        let mut retry = Retry::new(Open::default(), policy);
+        let outcome =
+            BlockingTransport::send(&mut retry, request(Kind::Authorize), &Backend::default());
+        assert!(matches!(outcome, Err(Error::CircuitOpen)));
+        assert_eq!(retry.transport().0, 1);
+    }
+
+    #[test]
+    fn retries_stop_at_the_deadline() {
+        let policy = RetryPolicy::new()
+            .max_retries(10)
+            .backoff(
+                Backoff::new(Duration::from_millis(20), Duration::from_millis(20)).jitter(false),
+            )
+            .deadline(Duration::from_millis(50));
+        let outcome = Err(ClientError::new(ClientErrorKind::Transport, "connection reset").into());
+        let delay = |retry, elapsed| {
+            policy.next_delay(
+                Some(Kind::Authorize),
+                retry,
+                Duration::from_millis(elapsed),
+                0,
+                &outcome,
+            )
+        };
+
+        assert_eq!(delay(0, 0), Some(Duration::from_millis(20)));
+        assert_eq!(delay(1, 30), Some(Duration::from_millis(20)));
+        assert_eq!(delay(2, 31), None);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn retry_asynchronously() {
+        let slept = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let record = slept.clone();
+        let policy = RetryPolicy::new()
+            .backoff(Backoff::new(Duration::from_millis(10), Duration::from_secs(1)).jitter(false));
+
+        let retry = Retry::new(Flaky::new(&[Some(504), None]), policy).with_sleep(
+            move |delay: Duration| {
+                record.lock().unwrap().push(delay);
+                core::future::ready(())
+            },
+        );
+        let outcome = Transport::send(&retry, request(Kind::Authorize), &Backend::default()).await;
+
+        assert_eq!(outcome.unwrap().status(), 200);
+        assert_eq!(retry.transport().attempts(), 3);
+        assert_eq!(
+            *slept.lock().unwrap(),
+            vec![Duration::from_millis(10), Duration::from_millis(20)]
+        );
+    }
+}
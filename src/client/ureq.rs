@@ -0,0 +1,27 @@
+use std::prelude::v1::*;
+
+use super::{BlockingTransport, RawResponse};
+use crate::{
+    http::{
+        request::{
+            ureq::{body_error, client_error},
+            SetupRequest,
+        },
+        Backend, Request,
+    },
+    Error,
+};
+
+impl BlockingTransport for ureq::Agent {
+    fn send(&mut self, request: Request, backend: &Backend) -> Result<RawResponse, Error> {
+        let response = match self.setup_request(request, backend)?.call() {
+            // ureq reports error statuses as errors, but their bodies are still of interest
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => return Err(client_error(e).into()),
+        };
+        let status = response.status();
+        let body = response.into_string().map_err(body_error)?;
+
+        Ok(RawResponse::new(status, body))
+    }
+}
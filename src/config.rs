@@ -0,0 +1,362 @@
+// Deserialization of 3scale proxy configurations.
+//
+// These are the documents System generates for each service, which Apicast uses to bootstrap
+// itself either from its configuration file or by querying System's proxy configs endpoint.
+// Only the subset of fields that have a use in this crate is deserialized, the rest is ignored.
+use std::prelude::v1::*;
+
+use core::convert::TryFrom;
+
+use serde::Deserialize;
+
+use crate::{
+    credentials::{Credentials, ServiceId},
+    http::{
+        extractor::{BackendVersion, Extractor, Location},
+        mapping_rule::{MappingRule, RuleSet},
+        router::Route,
+    },
+    service::Service,
+    Error, ParseError,
+};
+
+/// The configuration file used by Apicast, holding the settings of several services.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Config {
+    services: Vec<ServiceConfig>,
+}
+
+impl Config {
+    pub fn services(&self) -> &[ServiceConfig] {
+        self.services.as_slice()
+    }
+
+    pub fn service<S: AsRef<str>>(&self, service_id: S) -> Option<&ServiceConfig> {
+        self.services
+            .iter()
+            .find(|s| s.service_id().as_ref() == service_id.as_ref())
+    }
+
+    pub fn into_services(self) -> Vec<ServiceConfig> {
+        self.services
+    }
+}
+
+/// A proxy configuration as returned by System's proxy configs endpoints.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProxyConfig {
+    proxy_config: ProxyConfigContent,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ProxyConfigContent {
+    content: ServiceConfig,
+}
+
+impl ProxyConfig {
+    pub fn service_config(&self) -> &ServiceConfig {
+        &self.proxy_config.content
+    }
+
+    pub fn into_service_config(self) -> ServiceConfig {
+        self.proxy_config.content
+    }
+}
+
+/// The configuration of a single 3scale service.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawService")]
+pub struct ServiceConfig {
+    service_id: ServiceId,
+    credentials: Option<Credentials>,
+    extractor: Extractor,
+    rules: RuleSet,
+    hosts: Vec<String>,
+}
+
+impl ServiceConfig {
+    pub fn service_id(&self) -> &ServiceId {
+        &self.service_id
+    }
+
+    /// The credentials used to talk to the 3scale backend, if they were included.
+    pub fn credentials(&self) -> Option<&Credentials> {
+        self.credentials.as_ref()
+    }
+
+    /// Builds the `Service` to use in API calls, as long as credentials were included.
+    pub fn service(&self) -> Option<Service> {
+        self.credentials
+            .as_ref()
+            .map(|creds| Service::new(self.service_id.clone(), creds.clone()))
+    }
+
+    /// The settings to extract application credentials from incoming requests.
+    pub fn extractor(&self) -> &Extractor {
+        &self.extractor
+    }
+
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    /// The public host names this service is exposed at.
+    pub fn hosts(&self) -> &[String] {
+        self.hosts.as_slice()
+    }
+
+    /// Builds a `Route` for this service matching its hosts, as long as credentials were
+    /// included.
+    pub fn route(&self) -> Option<Route> {
+        let route = Route::new(self.service()?, self.rules.clone());
+
+        Some(
+            self.hosts
+                .iter()
+                .fold(route, |route, host| route.host(host)),
+        )
+    }
+}
+
+// Identifiers are numbers in System's output, but strings are accepted as well.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOrNumber {
+    String(String),
+    Number(u64),
+}
+
+impl From<StringOrNumber> for String {
+    fn from(son: StringOrNumber) -> Self {
+        match son {
+            StringOrNumber::String(s) => s,
+            StringOrNumber::Number(n) => n.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RawService {
+    id: StringOrNumber,
+    backend_version: Option<StringOrNumber>,
+    backend_authentication_type: Option<String>,
+    backend_authentication_value: Option<String>,
+    proxy: RawProxy,
+}
+
+#[derive(Deserialize)]
+struct RawProxy {
+    credentials_location: Option<String>,
+    auth_user_key: Option<String>,
+    auth_app_id: Option<String>,
+    auth_app_key: Option<String>,
+    #[serde(default)]
+    proxy_rules: Vec<MappingRule>,
+    #[serde(default)]
+    hosts: Vec<String>,
+}
+
+impl TryFrom<RawService> for ServiceConfig {
+    type Error = Error;
+
+    fn try_from(raw: RawService) -> Result<Self, Self::Error> {
+        let credentials = match (
+            raw.backend_authentication_type.as_deref(),
+            raw.backend_authentication_value,
+        ) {
+            (Some("service_token"), Some(token)) => Some(Credentials::from_token(token)),
+            (Some("provider_key"), Some(key)) => Some(Credentials::from_key(key)),
+            (Some(auth_type), Some(_)) => {
+                return Err(Error::Parse(ParseError::new(format!(
+                    "unknown backend authentication type {:?}",
+                    auth_type
+                ))))
+            }
+            _ => None,
+        };
+
+        let version = raw
+            .backend_version
+            .map_or(Ok(BackendVersion::UserKey), |v| String::from(v).parse())?;
+        let location = raw
+            .proxy
+            .credentials_location
+            .as_deref()
+            .map_or(Ok(Location::Query), |l| {
+                Location::for_backend_version(l, version)
+            })?;
+
+        let mut extractor = Extractor::new(version, location);
+        if let Some(name) = raw.proxy.auth_user_key {
+            extractor = extractor.user_key_name(name);
+        }
+        if let Some(name) = raw.proxy.auth_app_id {
+            extractor = extractor.app_id_name(name);
+        }
+        if let Some(name) = raw.proxy.auth_app_key {
+            extractor = extractor.app_key_name(name);
+        }
+
+        Ok(Self {
+            service_id: String::from(raw.id).into(),
+            credentials,
+            extractor,
+            rules: raw.proxy.proxy_rules.into(),
+            hosts: raw.proxy.hosts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::mapping_rule::Method;
+
+    const PROXY_CONFIG: &str = r#"{
+        "proxy_config": {
+            "id": 1,
+            "version": 3,
+            "environment": "production",
+            "content": {
+                "id": 2555417777820,
+                "account_id": 2445582535750,
+                "name": "API",
+                "backend_version": "2",
+                "backend_authentication_type": "service_token",
+                "backend_authentication_value": "a_service_token",
+                "proxy": {
+                    "hosts": ["api.example.com", "api-staging.example.com"],
+                    "credentials_location": "headers",
+                    "auth_app_id": "x-app-id",
+                    "auth_app_key": "x-app-key",
+                    "auth_user_key": "user_key",
+                    "api_backend": "https://backend.example.com:443",
+                    "proxy_rules": [
+                        {
+                            "http_method": "GET",
+                            "pattern": "/",
+                            "metric_system_name": "hits",
+                            "delta": 1,
+                            "last": false
+                        },
+                        {
+                            "http_method": "POST",
+                            "pattern": "/products/{id}/stock?warehouse={wh}",
+                            "metric_system_name": "stock_updates",
+                            "delta": 5,
+                            "last": true
+                        }
+                    ]
+                }
+            }
+        }
+    }"#;
+
+    #[test]
+    fn parse_proxy_config() {
+        let config = serde_json::from_str::<ProxyConfig>(PROXY_CONFIG)
+            .unwrap()
+            .into_service_config();
+
+        assert_eq!(config.service_id().as_ref(), "2555417777820");
+        assert_eq!(
+            config.credentials(),
+            Some(&Credentials::from_token("a_service_token"))
+        );
+        assert_eq!(
+            config.service(),
+            Some(Service::new(
+                "2555417777820",
+                Credentials::from_token("a_service_token")
+            ))
+        );
+        assert_eq!(
+            config.hosts(),
+            ["api.example.com", "api-staging.example.com"]
+        );
+
+        let extractor = config.extractor();
+        assert_eq!(extractor.backend_version(), BackendVersion::AppId);
+        assert_eq!(extractor.location(), Location::Headers);
+        assert_eq!(extractor.app_id(), "x-app-id");
+        assert_eq!(extractor.app_key(), "x-app-key");
+        assert_eq!(extractor.user_key(), "user_key");
+
+        let rules = config.rules();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(
+            rules.usage(&Method::POST, "/products/1/stock?warehouse=3"),
+            vec![("stock_updates", 5)]
+        );
+        assert_eq!(rules.usage(&Method::GET, "/products/1"), vec![("hits", 1)]);
+        assert_eq!(rules.usage(&Method::POST, "/products/1/stock"), vec![]);
+
+        let route = config.route().unwrap();
+        assert_eq!(route.hosts(), config.hosts());
+        assert_eq!(route.rules().len(), 2);
+    }
+
+    #[test]
+    fn parse_apicast_config_file() {
+        let json = r#"{
+            "services": [
+                {
+                    "id": "42",
+                    "backend_version": "oauth",
+                    "proxy": {
+                        "credentials_location": "authorization",
+                        "proxy_rules": []
+                    }
+                },
+                {
+                    "id": 43,
+                    "backend_version": 1,
+                    "backend_authentication_type": "provider_key",
+                    "backend_authentication_value": "a_provider_key",
+                    "proxy": {}
+                }
+            ]
+        }"#;
+        let config = serde_json::from_str::<Config>(json).unwrap();
+
+        assert_eq!(config.services().len(), 2);
+
+        let oauth = config.service("42").unwrap();
+        assert!(oauth.credentials().is_none());
+        assert!(oauth.service().is_none());
+        assert_eq!(oauth.extractor().backend_version(), BackendVersion::OAuth);
+        assert_eq!(oauth.extractor().location(), Location::Bearer);
+        assert!(oauth.rules().is_empty());
+
+        let user_key = config.service("43").unwrap();
+        assert_eq!(
+            user_key.credentials(),
+            Some(&Credentials::from_key("a_provider_key"))
+        );
+        assert_eq!(
+            user_key.extractor().backend_version(),
+            BackendVersion::UserKey
+        );
+        assert_eq!(user_key.extractor().location(), Location::Query);
+        assert_eq!(user_key.extractor().user_key(), "user_key");
+
+        assert!(config.service("44").is_none());
+    }
+
+    #[test]
+    fn reject_invalid_settings() {
+        let json = r#"{"id": 1, "backend_version": "3", "proxy": {}}"#;
+        assert!(serde_json::from_str::<ServiceConfig>(json).is_err());
+
+        let json = r#"{"id": 1, "proxy": {"credentials_location": "cookie"}}"#;
+        assert!(serde_json::from_str::<ServiceConfig>(json).is_err());
+
+        let json = r#"{
+            "id": 1,
+            "backend_authentication_type": "password",
+            "backend_authentication_value": "secret",
+            "proxy": {}
+        }"#;
+        assert!(serde_json::from_str::<ServiceConfig>(json).is_err());
+    }
+}
@@ -0,0 +1,434 @@
+use std::prelude::v1::*;
+
+use core::fmt;
+
+#[cfg(feature = "extractor")]
+use crate::http::extractor::ExtractorError;
+#[cfg(feature = "rest-mappings")]
+use crate::http::mapping_rule::EscapingError;
+use crate::http::HeaderError;
+
+/// Errors returned by this crate.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// An API call was built without specifying its kind.
+    MissingKind,
+    /// A header name or value is not valid.
+    InvalidHeader(HeaderError),
+    /// An HTTP method is not supported or not valid.
+    InvalidMethod(String),
+    /// A value could not be parsed, such as a backend URL or a configuration document.
+    Parse(ParseError),
+    /// An HTTP client failed to set up or send a request, or to receive its response.
+    Client(ClientError),
+    /// A circuit breaker rejected the call to the backend.
+    CircuitOpen,
+    /// A response is not a valid 3scale XML document.
+    Xml(XmlError),
+    /// Credentials could not be extracted from an incoming request.
+    #[cfg(feature = "extractor")]
+    Extractor(ExtractorError),
+    /// A mapping rule pattern could not be compiled into a regular expression.
+    #[cfg(feature = "rest-mappings")]
+    MappingRule(EscapingError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingKind => f.write_str("missing kind of API call"),
+            Self::InvalidHeader(e) => e.fmt(f),
+            Self::InvalidMethod(method) => write!(f, "invalid or unsupported method {}", method),
+            Self::Parse(e) => e.fmt(f),
+            Self::Client(e) => e.fmt(f),
+            Self::CircuitOpen => f.write_str("circuit breaker is open"),
+            Self::Xml(e) => e.fmt(f),
+            #[cfg(feature = "extractor")]
+            Self::Extractor(e) => e.fmt(f),
+            #[cfg(feature = "rest-mappings")]
+            Self::MappingRule(e) => write!(f, "invalid mapping rule pattern: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Parse(e) => Some(e),
+            Self::Client(e) => Some(e),
+            Self::InvalidHeader(e) => Some(e),
+            Self::Xml(e) => Some(e),
+            #[cfg(feature = "extractor")]
+            Self::Extractor(e) => Some(e),
+            #[cfg(feature = "rest-mappings")]
+            Self::MappingRule(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Error {
+    /// Whether the error comes from sending a request to the backend or receiving its response,
+    /// as opposed to setting up the request or interpreting the response.
+    pub fn is_transport(&self) -> bool {
+        matches!(self, Self::Client(e) if e.kind().is_transport())
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Self::Parse(e)
+    }
+}
+
+impl From<ClientError> for Error {
+    fn from(e: ClientError) -> Self {
+        Self::Client(e)
+    }
+}
+
+impl From<HeaderError> for Error {
+    fn from(e: HeaderError) -> Self {
+        Self::InvalidHeader(e)
+    }
+}
+
+impl From<XmlError> for Error {
+    fn from(e: XmlError) -> Self {
+        Self::Xml(e)
+    }
+}
+
+#[cfg(feature = "extractor")]
+impl From<ExtractorError> for Error {
+    fn from(e: ExtractorError) -> Self {
+        Self::Extractor(e)
+    }
+}
+
+#[cfg(feature = "rest-mappings")]
+impl From<EscapingError> for Error {
+    fn from(e: EscapingError) -> Self {
+        Self::MappingRule(e)
+    }
+}
+
+#[cfg(feature = "std")]
+type Source = Box<dyn std::error::Error + Send + Sync>;
+
+/// An error parsing a value, along with the error that caused it when there is one.
+#[derive(Debug)]
+pub struct ParseError {
+    message: String,
+    #[cfg(feature = "std")]
+    source: Option<Source>,
+}
+
+impl ParseError {
+    pub fn new<S: Into<String>>(message: S) -> Self {
+        Self {
+            message: message.into(),
+            #[cfg(feature = "std")]
+            source: None,
+        }
+    }
+
+    /// Keeps the error that caused this one, which is returned by `source()`.
+    #[cfg(feature = "std")]
+    pub fn with_source<E: Into<Source>>(mut self, source: E) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    pub fn message(&self) -> &str {
+        self.message.as_str()
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message.as_str())?;
+        #[cfg(feature = "std")]
+        if let Some(source) = &self.source {
+            write!(f, ": {}", source)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|e| &**e as &(dyn std::error::Error + 'static))
+    }
+}
+
+/// What an HTTP client failed to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ClientErrorKind {
+    /// The request could not be set up, ie. the backend URI or an option was rejected.
+    Request,
+    /// No connection could be established with the backend.
+    Connect,
+    /// The backend did not answer in time.
+    Timeout,
+    /// The request or its response could not be transferred.
+    Transport,
+    /// The response could not be interpreted, ie. it is not a 3scale response.
+    Response,
+}
+
+impl ClientErrorKind {
+    /// Whether the error happened while talking to the backend. These are the errors that could
+    /// go away by sending the same request again.
+    pub fn is_transport(self) -> bool {
+        matches!(self, Self::Connect | Self::Timeout | Self::Transport)
+    }
+}
+
+/// An error from an HTTP client, along with the error of the underlying library when there is
+/// one.
+#[derive(Debug)]
+pub struct ClientError {
+    kind: ClientErrorKind,
+    message: String,
+    #[cfg(feature = "std")]
+    source: Option<Source>,
+}
+
+impl ClientError {
+    pub fn new<S: Into<String>>(kind: ClientErrorKind, message: S) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            #[cfg(feature = "std")]
+            source: None,
+        }
+    }
+
+    /// Keeps the error that caused this one, which is returned by `source()`.
+    #[cfg(feature = "std")]
+    pub fn with_source<E: Into<Source>>(mut self, source: E) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    // 3scale answers with a document whatever the status, so one that cannot be parsed most
+    // likely comes from something in between, like a proxy.
+    #[cfg(feature = "xml-response-alloc")]
+    pub(crate) fn unexpected_status(status: u16, e: XmlError) -> Self {
+        #[cfg(feature = "std")]
+        let error = Self::new(
+            ClientErrorKind::Response,
+            format!("unexpected response with status {}", status),
+        )
+        .with_source(e);
+        #[cfg(not(feature = "std"))]
+        let error = Self::new(
+            ClientErrorKind::Response,
+            format!("unexpected response with status {}: {}", status, e),
+        );
+
+        error
+    }
+
+    pub fn kind(&self) -> ClientErrorKind {
+        self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        self.message.as_str()
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message.as_str())?;
+        #[cfg(feature = "std")]
+        if let Some(source) = &self.source {
+            write!(f, ": {}", source)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|e| &**e as &(dyn std::error::Error + 'static))
+    }
+}
+
+/// An error parsing an XML document, with its location when known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlError {
+    line: Option<u64>,
+    column: Option<u64>,
+    message: String,
+}
+
+impl XmlError {
+    pub fn new<S: Into<String>>(message: S) -> Self {
+        Self {
+            line: None,
+            column: None,
+            message: message.into(),
+        }
+    }
+
+    /// Sets the location of the error, with lines and columns counting from 1.
+    pub fn at(mut self, line: u64, column: u64) -> Self {
+        self.line = Some(line);
+        self.column = Some(column);
+        self
+    }
+
+    pub fn line(&self) -> Option<u64> {
+        self.line
+    }
+
+    pub fn column(&self) -> Option<u64> {
+        self.column
+    }
+
+    pub fn message(&self) -> &str {
+        self.message.as_str()
+    }
+}
+
+impl fmt::Display for XmlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                write!(f, "invalid XML at {}:{}: {}", line, column, self.message)
+            }
+            _ => write!(f, "invalid XML: {}", self.message),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for XmlError {}
+
+#[cfg(feature = "xml-response")]
+impl From<serde_xml_rs::Error> for XmlError {
+    fn from(e: serde_xml_rs::Error) -> Self {
+        use xml::common::Position;
+
+        match e {
+            serde_xml_rs::Error::Syntax { source } => {
+                let position = source.position();
+                Self::new(source.msg()).at(position.row + 1, position.column + 1)
+            }
+            e => Self::new(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_errors() {
+        assert_eq!(Error::MissingKind.to_string(), "missing kind of API call");
+        assert_eq!(
+            Error::from(HeaderError::InvalidName("a b".into())).to_string(),
+            r#"invalid header name "a b""#
+        );
+        assert_eq!(
+            Error::from(XmlError::new("unexpected end").at(3, 14)).to_string(),
+            "invalid XML at 3:14: unexpected end"
+        );
+    }
+
+    #[test]
+    fn transport_errors() {
+        let e = Error::from(ClientError::new(ClientErrorKind::Timeout, "timed out"));
+        assert!(e.is_transport());
+        assert_eq!(e.to_string(), "timed out");
+
+        let e = Error::from(ClientError::new(ClientErrorKind::Request, "invalid URI"));
+        assert!(!e.is_transport());
+        assert!(!Error::from(ParseError::new("invalid")).is_transport());
+        assert!(!Error::CircuitOpen.is_transport());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn client_errors_keep_their_source() {
+        use std::{error::Error as _, io};
+
+        let refused = io::Error::new(io::ErrorKind::ConnectionRefused, "connection refused");
+        let e = Error::from(
+            ClientError::new(ClientErrorKind::Connect, "failed to send request")
+                .with_source(refused),
+        );
+        assert_eq!(e.to_string(), "failed to send request: connection refused");
+
+        let source = e.source().unwrap().source().unwrap();
+        assert_eq!(
+            source.downcast_ref::<io::Error>().unwrap().kind(),
+            io::ErrorKind::ConnectionRefused
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn wrapped_errors_are_sources() {
+        use std::error::Error as _;
+
+        let e = Error::from(HeaderError::InvalidName("a b".into()));
+        assert_eq!(
+            e.source().unwrap().to_string(),
+            r#"invalid header name "a b""#
+        );
+
+        let e = Error::from(XmlError::new("unexpected end").at(3, 14));
+        assert_eq!(
+            e.source().unwrap().to_string(),
+            "invalid XML at 3:14: unexpected end"
+        );
+
+        #[cfg(feature = "extractor")]
+        {
+            let e = Error::from(ExtractorError::InvalidAuthorization);
+            assert_eq!(
+                e.source().unwrap().to_string(),
+                "invalid Authorization header"
+            );
+        }
+        #[cfg(feature = "rest-mappings")]
+        {
+            let e = Error::from(EscapingError::RegexTooBig);
+            assert_eq!(
+                e.source().unwrap().to_string(),
+                "regex requires too much memory"
+            );
+        }
+    }
+
+    #[cfg(feature = "xml-response-alloc")]
+    #[test]
+    fn locate_xml_syntax_errors() {
+        use core::str::FromStr;
+
+        let e = crate::response::Authorization::from_str("<status>\n  <authorized>true</status>")
+            .unwrap_err();
+
+        match e {
+            Error::Xml(e) => {
+                assert_eq!(e.line(), Some(2));
+                assert!(e.column().is_some());
+            }
+            e => core::panic!("unexpected error {:?}", e),
+        }
+    }
+}
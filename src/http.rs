@@ -1,6 +1,7 @@
 use std::prelude::v1::*;
 
-use std::collections::{btree_map::Iter as InnerIter, BTreeMap};
+use core::{convert::TryFrom, fmt, slice};
+use std::collections::BTreeMap;
 
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -29,23 +30,158 @@ impl Method {
     }
 }
 
+/// Error returned when a header name or value does not conform to RFC 7230.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct HeaderMap(BTreeMap<String, String>);
+#[non_exhaustive]
+pub enum HeaderError {
+    /// The header name is empty or contains characters other than token characters.
+    InvalidName(String),
+    /// The value of the named header contains control characters, ie. line breaks. The value
+    /// itself is left out since it could hold credentials.
+    InvalidValue(String),
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidName(name) => write!(f, "invalid header name {:?}", name),
+            Self::InvalidValue(name) => write!(f, "invalid value for header {:?}", name),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for HeaderError {}
+
+// RFC 7230 section 3.2.6: token = 1*tchar
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+// RFC 7230 section 3.2: field-value = *( field-content / obs-fold ), where field-content is made
+// of visible characters, spaces, tabs and obs-text. Folding is deprecated and not accepted.
+fn is_valid_value(value: &str) -> bool {
+    value
+        .bytes()
+        .all(|b| b == b'\t' || (b >= 0x20 && b != 0x7f))
+}
+
+/// A map of HTTP headers.
+///
+/// Header names are case insensitive and keep the casing they were first added with. Names can
+/// have several values, which are kept in the order they were added.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct HeaderMap(Vec<(String, String)>);
 
 impl HeaderMap {
     pub fn new() -> Self {
-        Self(BTreeMap::new())
+        Self(Vec::new())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self(Vec::with_capacity(capacity))
+    }
+
+    fn validate(name: &str, value: &str) -> Result<(), HeaderError> {
+        if !is_valid_name(name) {
+            Err(HeaderError::InvalidName(name.to_owned()))
+        } else if !is_valid_value(value) {
+            Err(HeaderError::InvalidValue(name.to_owned()))
+        } else {
+            Ok(())
+        }
+    }
+
+    // Existing casing for a name, so that all values of a header are sent under the same name.
+    fn name_for(&self, name: String) -> String {
+        self.0
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name.as_str()))
+            .map_or(name, |(k, _)| k.clone())
+    }
+
+    /// Sets the value of a header, replacing any values it had. Returns the first of these.
+    pub fn insert<K: Into<String>, V: Into<String>>(
+        &mut self,
+        name: K,
+        value: V,
+    ) -> Result<Option<String>, HeaderError> {
+        let (name, value) = (name.into(), value.into());
+        Self::validate(name.as_str(), value.as_str())?;
+
+        let name = self.name_for(name);
+        let previous = self.remove(name.as_str());
+        self.0.push((name, value));
+
+        Ok(previous)
+    }
+
+    /// Adds a value to a header, keeping the values it had.
+    pub fn append<K: Into<String>, V: Into<String>>(
+        &mut self,
+        name: K,
+        value: V,
+    ) -> Result<(), HeaderError> {
+        let (name, value) = (name.into(), value.into());
+        Self::validate(name.as_str(), value.as_str())?;
+
+        let name = self.name_for(name);
+        self.0.push((name, value));
+
+        Ok(())
+    }
+
+    // For names and values known to be valid.
+    pub(crate) fn insert_unchecked<K: Into<String>, V: Into<String>>(&mut self, name: K, value: V) {
+        let name = self.name_for(name.into());
+        let _ = self.remove(name.as_str());
+        self.0.push((name, value.into()));
+    }
+
+    pub(crate) fn append_unchecked<K: Into<String>, V: Into<String>>(&mut self, name: K, value: V) {
+        let name = self.name_for(name.into());
+        self.0.push((name, value.into()));
+    }
+
+    /// Returns the first value of a header.
+    pub fn get<K: AsRef<str>>(&self, name: K) -> Option<&str> {
+        self.get_all(name).next()
+    }
+
+    /// Returns all the values of a header.
+    pub fn get_all<K: AsRef<str>>(&self, name: K) -> impl Iterator<Item = &str> {
+        self.0.iter().filter_map(move |(k, v)| {
+            if k.eq_ignore_ascii_case(name.as_ref()) {
+                Some(v.as_str())
+            } else {
+                None
+            }
+        })
     }
 
-    // There's no provision for this method in the inner map
-    pub fn with_capacity(_: usize) -> Self {
-        Self::new()
+    pub fn contains_key<K: AsRef<str>>(&self, name: K) -> bool {
+        self.get(name).is_some()
     }
 
-    pub fn insert(&mut self, key: String, value: String) -> Option<String> {
-        self.0.insert(key, value)
+    /// Removes all the values of a header, returning the first of them.
+    pub fn remove<K: AsRef<str>>(&mut self, name: K) -> Option<String> {
+        let mut first = None;
+
+        self.0.retain(|(k, v)| {
+            let matches = k.eq_ignore_ascii_case(name.as_ref());
+            if matches && first.is_none() {
+                first = Some(v.clone());
+            }
+            !matches
+        });
+
+        first
     }
 
+    /// The number of values in the map, counting each value of a header.
     pub fn len(&self) -> usize {
         self.0.len()
     }
@@ -54,6 +190,18 @@ impl HeaderMap {
         self.len() == 0
     }
 
+    /// Adds values from an iterator of names and values, stopping at the first invalid one.
+    pub fn try_extend<K, V, I>(&mut self, iter: I) -> Result<(), HeaderError>
+    where
+        K: Into<String>,
+        V: Into<String>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        iter.into_iter()
+            .try_for_each(|(name, value)| self.append(name, value))
+    }
+
+    /// Iterates over names and values, yielding a name once for each of its values.
     pub fn iter(&self) -> Iter<'_> {
         Iter {
             iter: self.0.iter(),
@@ -61,34 +209,39 @@ impl HeaderMap {
     }
 }
 
-impl Default for HeaderMap {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 #[derive(Debug, Clone)]
 pub struct Iter<'a> {
-    iter: InnerIter<'a, String, String>,
+    iter: slice::Iter<'a, (String, String)>,
 }
 
 impl<'a> Iterator for Iter<'a> {
-    type Item = <InnerIter<'a, String, String> as Iterator>::Item;
+    type Item = (&'a str, &'a str);
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next()
+        self.iter.next().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+impl<'a> IntoIterator for &'a HeaderMap {
+    type IntoIter = Iter<'a>;
+    type Item = (&'a str, &'a str);
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
     }
 }
 
 impl IntoIterator for HeaderMap {
-    type IntoIter = <BTreeMap<String, String> as IntoIterator>::IntoIter;
-    type Item = <BTreeMap<String, String> as IntoIterator>::Item;
+    type IntoIter = <Vec<(String, String)> as IntoIterator>::IntoIter;
+    type Item = <Vec<(String, String)> as IntoIterator>::Item;
 
     fn into_iter(self) -> Self::IntoIter {
         self.0.into_iter()
     }
 }
 
+/// Collects headers without validating them. Use `try_extend` for headers coming from untrusted
+/// input.
 impl<S: ToString> std::iter::FromIterator<(S, S)> for HeaderMap {
     fn from_iter<T: IntoIterator<Item = (S, S)>>(iter: T) -> Self {
         let mut map = Self::new();
@@ -98,25 +251,119 @@ impl<S: ToString> std::iter::FromIterator<(S, S)> for HeaderMap {
     }
 }
 
+/// Adds headers without validating them. Use `try_extend` for headers coming from untrusted input.
 impl<S: ToString> Extend<(S, S)> for HeaderMap {
     fn extend<T: IntoIterator<Item = (S, S)>>(&mut self, iter: T) {
-        for (key, value) in iter {
-            self.insert(key.to_string(), value.to_string());
+        for (name, value) in iter {
+            self.append_unchecked(name.to_string(), value.to_string());
         }
     }
 }
 
-impl From<BTreeMap<String, String>> for HeaderMap {
-    fn from(map: BTreeMap<String, String>) -> Self {
-        HeaderMap(map)
+impl TryFrom<BTreeMap<String, String>> for HeaderMap {
+    type Error = HeaderError;
+
+    fn try_from(map: BTreeMap<String, String>) -> Result<Self, Self::Error> {
+        let mut headers = Self::with_capacity(map.len());
+
+        headers.try_extend(map)?;
+        Ok(headers)
     }
 }
 
 mod parameters;
 pub use self::parameters::Parameters;
+pub mod backend;
+pub use self::backend::Backend;
 pub mod endpoints;
+#[cfg(feature = "extractor")]
+pub mod extractor;
 pub mod request;
 pub use self::request::Request;
 
 #[cfg(feature = "rest-mappings")]
 pub mod mapping_rule;
+#[cfg(feature = "rest-mappings")]
+pub mod router;
+#[cfg(feature = "tower")]
+pub mod tower;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_names_are_case_insensitive() {
+        let mut headers = HeaderMap::with_capacity(4);
+
+        assert_eq!(headers.insert("User-Agent", "a").unwrap(), None);
+        assert_eq!(
+            headers.insert("user-agent", "b").unwrap(),
+            Some("a".to_string())
+        );
+        headers.append("Accept", "text/xml").unwrap();
+        headers.append("ACCEPT", "application/json").unwrap();
+
+        assert_eq!(headers.len(), 3);
+        assert_eq!(headers.get("USER-AGENT"), Some("b"));
+        assert_eq!(
+            headers.get_all("accept").collect::<Vec<_>>(),
+            vec!["text/xml", "application/json"]
+        );
+        assert_eq!(
+            headers.iter().collect::<Vec<_>>(),
+            vec![
+                ("User-Agent", "b"),
+                ("Accept", "text/xml"),
+                ("Accept", "application/json")
+            ]
+        );
+
+        assert_eq!(headers.remove("Accept"), Some("text/xml".to_string()));
+        assert!(!headers.contains_key("accept"));
+        assert_eq!(headers.remove("accept"), None);
+        assert_eq!(headers.len(), 1);
+    }
+
+    #[test]
+    fn invalid_headers_are_rejected() {
+        let mut headers = HeaderMap::new();
+
+        assert_eq!(
+            headers.insert("X-Header\r\nInjected", "v"),
+            Err(HeaderError::InvalidName("X-Header\r\nInjected".to_string()))
+        );
+        assert_eq!(
+            headers.append("", "v"),
+            Err(HeaderError::InvalidName(String::new()))
+        );
+        assert_eq!(
+            headers.append("X-Header", "v\r\nInjected: 1"),
+            Err(HeaderError::InvalidValue("X-Header".to_string()))
+        );
+        assert_eq!(
+            headers.append("X-Header", "\0"),
+            Err(HeaderError::InvalidValue("X-Header".to_string()))
+        );
+        assert!(headers.is_empty());
+
+        assert!(headers.append("X-Header", "tab\tand obs-text é").is_ok());
+
+        let map = [("a b".to_string(), "v".to_string())]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        assert!(HeaderMap::try_from(map).is_err());
+    }
+
+    #[test]
+    fn collect_headers() {
+        let headers = [("X-Trace", "a"), ("x-trace", "b"), ("Accept", "text/xml")]
+            .into_iter()
+            .collect::<HeaderMap>();
+
+        assert_eq!(
+            headers.iter().collect::<Vec<_>>(),
+            vec![("X-Trace", "a"), ("X-Trace", "b"), ("Accept", "text/xml")]
+        );
+    }
+}
@@ -0,0 +1,281 @@
+use std::prelude::v1::*;
+
+use core::{fmt, str::FromStr};
+
+use super::Request;
+use crate::{Error, ParseError};
+
+/// The default host of the 3scale SaaS Service Management API.
+pub const DEFAULT_HOST: &str = "su1.3scale.net";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Scheme {
+    Http,
+    Https,
+}
+
+impl Scheme {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Scheme::Http => "http",
+            Scheme::Https => "https",
+        }
+    }
+
+    pub fn default_port(&self) -> u16 {
+        match self {
+            Scheme::Http => 80,
+            Scheme::Https => 443,
+        }
+    }
+}
+
+/// The location of a 3scale backend, used to build the full URIs of requests.
+///
+/// A path prefix can be set for backends reachable only under some path, such as behind an
+/// ingress shared with other services. The backend is displayed as its base URL, so it can be
+/// passed to any client integration taking one.
+///
+/// # Examples
+///
+/// ```
+/// use threescalers::http::{backend::Backend, Request};
+/// # use threescalers::{
+/// #     api_call::{ApiCall, Kind},
+/// #     application::Application,
+/// #     credentials::Credentials,
+/// #     service::Service,
+/// #     transaction::Transaction,
+/// # };
+/// # let service = Service::new("my_service_id", Credentials::from_token("my_token"));
+/// # let app = Application::from_user_key("my_user_key");
+/// # let txns = [Transaction::new(&app, None, None, None)];
+/// # let mut builder = ApiCall::builder(&service);
+/// # let apicall = builder.transactions(&txns).kind(Kind::Authorize).build().unwrap();
+///
+/// let backend = "http://backend.example.com:3000/3scale/".parse::<Backend>().unwrap();
+/// assert_eq!(backend.to_string(), "http://backend.example.com:3000/3scale");
+///
+/// let request = Request::from(&apicall);
+/// assert!(backend
+///     .uri_for(&request)
+///     .starts_with("http://backend.example.com:3000/3scale/transactions/authorize.xml?"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backend {
+    scheme: Scheme,
+    host: String,
+    port: Option<u16>,
+    prefix: String,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self::new(DEFAULT_HOST)
+    }
+}
+
+impl Backend {
+    /// Creates a backend reached through HTTPS on the default port.
+    pub fn new<S: Into<String>>(host: S) -> Self {
+        Self {
+            scheme: Scheme::Https,
+            host: host.into(),
+            port: None,
+            prefix: String::new(),
+        }
+    }
+
+    pub fn with_scheme(mut self, scheme: Scheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Sets the path under which the backend's endpoints are found.
+    pub fn with_prefix<S: AsRef<str>>(mut self, prefix: S) -> Self {
+        let prefix = prefix.as_ref().trim_matches('/');
+
+        self.prefix = if prefix.is_empty() {
+            String::new()
+        } else {
+            ["/", prefix].concat()
+        };
+        self
+    }
+
+    pub fn scheme(&self) -> Scheme {
+        self.scheme
+    }
+
+    pub fn host(&self) -> &str {
+        self.host.as_str()
+    }
+
+    /// The port, which is the scheme's default one unless set.
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or_else(|| self.scheme.default_port())
+    }
+
+    pub fn prefix(&self) -> &str {
+        self.prefix.as_str()
+    }
+
+    /// Builds the full URI of a request to this backend.
+    pub fn uri_for(&self, request: &Request) -> String {
+        let path_and_query = request.parameters.path_and_query(request.path);
+        [self.to_string().as_str(), path_and_query.as_ref()].concat()
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}", self.scheme.as_str(), self.host)?;
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        f.write_str(self.prefix.as_str())
+    }
+}
+
+impl FromStr for Backend {
+    type Err = Error;
+
+    /// Parses a base URL such as `https://su1.3scale.net` or `http://[::1]:3000/prefix`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scheme, rest) = s.split_once("://").ok_or_else(|| {
+            Error::Parse(ParseError::new(format!(
+                "missing scheme in backend URL {:?}",
+                s
+            )))
+        })?;
+        let scheme = match scheme.to_ascii_lowercase().as_str() {
+            "http" => Scheme::Http,
+            "https" => Scheme::Https,
+            other => {
+                return Err(Error::Parse(ParseError::new(format!(
+                    "unsupported scheme {:?} in backend URL",
+                    other
+                ))))
+            }
+        };
+
+        if rest.contains(['?', '#', '@']) {
+            return Err(Error::Parse(ParseError::new(format!(
+                "backend URL {:?} can only have a host, a port and a path",
+                s
+            ))));
+        }
+
+        let (authority, prefix) = rest.find('/').map_or((rest, ""), |idx| rest.split_at(idx));
+        // the port follows the last colon unless it is part of an IPv6 literal
+        let (host, port) = match authority.rfind(':') {
+            Some(idx) if !authority[idx..].contains(']') => {
+                let port = authority[idx + 1..].parse::<u16>().map_err(|e| {
+                    Error::Parse(ParseError::new(format!(
+                        "invalid port in backend URL {:?}: {}",
+                        s, e
+                    )))
+                })?;
+                (&authority[..idx], Some(port))
+            }
+            _ => (authority, None),
+        };
+
+        if host.is_empty() {
+            return Err(Error::Parse(ParseError::new(format!(
+                "missing host in backend URL {:?}",
+                s
+            ))));
+        }
+
+        let mut backend = Backend::new(host).with_scheme(scheme).with_prefix(prefix);
+        backend.port = port;
+
+        Ok(backend)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_backends() {
+        let cases = [
+            (
+                "https://su1.3scale.net",
+                Scheme::Https,
+                "su1.3scale.net",
+                443,
+                "",
+            ),
+            (
+                "HTTP://localhost:3000/",
+                Scheme::Http,
+                "localhost",
+                3000,
+                "",
+            ),
+            (
+                "http://[::1]:8080/a/b/",
+                Scheme::Http,
+                "[::1]",
+                8080,
+                "/a/b",
+            ),
+            (
+                "https://[::1]/3scale",
+                Scheme::Https,
+                "[::1]",
+                443,
+                "/3scale",
+            ),
+        ];
+
+        for (url, scheme, host, port, prefix) in cases {
+            let backend = url.parse::<Backend>().unwrap();
+            assert_eq!(
+                (
+                    backend.scheme(),
+                    backend.host(),
+                    backend.port(),
+                    backend.prefix()
+                ),
+                (scheme, host, port, prefix),
+                "{}",
+                url
+            );
+        }
+
+        for url in [
+            "su1.3scale.net",
+            "ftp://host",
+            "https://",
+            "https://:443",
+            "https://host:port",
+            "https://host/path?query",
+            "https://user@host",
+        ] {
+            assert!(url.parse::<Backend>().is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn display_base_url() {
+        assert_eq!(Backend::default().to_string(), "https://su1.3scale.net");
+        assert_eq!(
+            Backend::new("localhost")
+                .with_scheme(Scheme::Http)
+                .with_port(3000)
+                .with_prefix("/3scale/")
+                .to_string(),
+            "http://localhost:3000/3scale"
+        );
+        assert_eq!(Backend::new("h").with_prefix("/").to_string(), "https://h");
+    }
+}
@@ -0,0 +1,571 @@
+// Extraction of 3scale application credentials from incoming requests, configured with the
+// settings describing where and how they are carried, mirroring the "authentication" settings
+// of a 3scale service.
+use std::prelude::v1::*;
+
+use core::fmt;
+use std::{borrow::Cow, str::FromStr};
+
+use crate::{application::Application, user::User, Error, ParseError};
+
+/// The authentication mode of a 3scale service, historically known as its "backend version".
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BackendVersion {
+    /// Backend version 1: a single `user_key`.
+    UserKey,
+    /// Backend version 2: an `app_id` with an optional `app_key`.
+    AppId,
+    /// OAuth 2.0 access tokens.
+    OAuth,
+    /// OpenID Connect, carrying the application in a JWT.
+    Oidc,
+}
+
+impl BackendVersion {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::UserKey => "1",
+            Self::AppId => "2",
+            Self::OAuth => "oauth",
+            Self::Oidc => "oidc",
+        }
+    }
+
+    /// Whether credentials are tokens that should be looked up in the `Authorization` header
+    /// using the Bearer scheme rather than HTTP Basic authentication.
+    pub fn uses_tokens(&self) -> bool {
+        matches!(self, Self::OAuth | Self::Oidc)
+    }
+}
+
+impl FromStr for BackendVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" => Ok(Self::UserKey),
+            "2" => Ok(Self::AppId),
+            "oauth" => Ok(Self::OAuth),
+            "oidc" => Ok(Self::Oidc),
+            _ => Err(Error::Parse(ParseError::new(format!(
+                "unknown backend version {:?}",
+                s
+            )))),
+        }
+    }
+}
+
+/// Where an incoming request carries the credentials.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Location {
+    /// Query string parameters.
+    Query,
+    /// Request headers named after the credential parameters.
+    Headers,
+    /// The `Authorization` header using the HTTP Basic scheme.
+    Basic,
+    /// The `Authorization` header using the Bearer scheme.
+    Bearer,
+}
+
+impl Location {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Query => "query",
+            Self::Headers => "headers",
+            Self::Basic => "basic",
+            Self::Bearer => "bearer",
+        }
+    }
+
+    /// Interprets a 3scale `credentials_location` value for a given backend version.
+    ///
+    /// 3scale just says "authorization" for credentials in the `Authorization` header, which
+    /// means HTTP Basic for keys and Bearer for tokens.
+    pub fn for_backend_version(location: &str, version: BackendVersion) -> Result<Self, Error> {
+        match location {
+            "authorization" if version.uses_tokens() => Ok(Self::Bearer),
+            "authorization" => Ok(Self::Basic),
+            other => other.parse(),
+        }
+    }
+}
+
+impl FromStr for Location {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "query" => Ok(Self::Query),
+            "headers" => Ok(Self::Headers),
+            "basic" => Ok(Self::Basic),
+            "bearer" => Ok(Self::Bearer),
+            _ => Err(Error::Parse(ParseError::new(format!(
+                "unknown credentials location {:?}",
+                s
+            )))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExtractorError {
+    /// The required credential, named as the parameter it was looked up as, was not found.
+    MissingCredentials(String),
+    /// The `Authorization` header could not be decoded.
+    InvalidAuthorization,
+}
+
+impl fmt::Display for ExtractorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingCredentials(name) => write!(f, "missing credentials: {}", name),
+            Self::InvalidAuthorization => f.write_str("invalid Authorization header"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ExtractorError {}
+
+/// Credential extraction settings for incoming requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extractor {
+    version: BackendVersion,
+    location: Location,
+    user_key: String,
+    app_id: String,
+    app_key: String,
+    access_token: String,
+    user_id: Option<String>,
+}
+
+// Credentials found while scanning a request, first occurrence wins.
+#[derive(Debug, Default)]
+struct Found {
+    user_key: Option<String>,
+    app_id: Option<String>,
+    app_key: Option<String>,
+    access_token: Option<String>,
+    user_id: Option<String>,
+}
+
+impl Extractor {
+    /// Creates an `Extractor` using the default 3scale parameter names.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threescalers::http::extractor::*;
+    ///
+    /// let extractor = Extractor::new(BackendVersion::AppId, Location::Headers)
+    ///     .app_id_name("x-app-id")
+    ///     .app_key_name("x-app-key");
+    /// ```
+    pub fn new(version: BackendVersion, location: Location) -> Self {
+        Self {
+            version,
+            location,
+            user_key: "user_key".into(),
+            app_id: "app_id".into(),
+            app_key: "app_key".into(),
+            access_token: "access_token".into(),
+            user_id: None,
+        }
+    }
+
+    pub fn user_key_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.user_key = name.into();
+        self
+    }
+
+    pub fn app_id_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.app_id = name.into();
+        self
+    }
+
+    pub fn app_key_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.app_key = name.into();
+        self
+    }
+
+    pub fn access_token_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.access_token = name.into();
+        self
+    }
+
+    /// Sets the name of a parameter carrying a 3scale `user_id`, which is not looked up otherwise.
+    /// It is only found in query strings and headers.
+    pub fn user_id_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.user_id = Some(name.into());
+        self
+    }
+
+    pub fn backend_version(&self) -> BackendVersion {
+        self.version
+    }
+
+    pub fn location(&self) -> Location {
+        self.location
+    }
+
+    pub fn user_key(&self) -> &str {
+        self.user_key.as_str()
+    }
+
+    pub fn app_id(&self) -> &str {
+        self.app_id.as_str()
+    }
+
+    pub fn app_key(&self) -> &str {
+        self.app_key.as_str()
+    }
+
+    pub fn access_token(&self) -> &str {
+        self.access_token.as_str()
+    }
+
+    pub fn user_id(&self) -> Option<&str> {
+        self.user_id.as_deref()
+    }
+
+    /// Extracts the application credentials, and a user if configured, from the path and query
+    /// string and the headers of an incoming request.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use threescalers::{application::Application, http::extractor::*};
+    ///
+    /// let extractor = Extractor::new(BackendVersion::AppId, Location::Query);
+    /// let (app, user) = extractor
+    ///     .extract("/products?app_id=my_app&app_key=my_key", core::iter::empty::<(&str, &str)>())
+    ///     .unwrap();
+    ///
+    /// assert_eq!(app, Application::from_app_id_and_key("my_app", "my_key"));
+    /// assert!(user.is_none());
+    /// ```
+    pub fn extract<P, I, K, V>(
+        &self,
+        path_qs: P,
+        headers: I,
+    ) -> Result<(Application, Option<User>), ExtractorError>
+    where
+        P: AsRef<str>,
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let found = match self.location {
+            Location::Query => {
+                let qs = path_qs.as_ref().split_once('?').map_or("", |(_, qs)| qs);
+                self.scan(
+                    qs.split('&').filter_map(|kv| {
+                        let (k, v) = kv.split_once('=')?;
+                        Some((decode_query_component(k), decode_query_component(v)))
+                    }),
+                    false,
+                )
+            }
+            Location::Headers => self.scan(headers, true),
+            Location::Basic => self.scan_basic(authorization_value(headers, "basic").as_deref())?,
+            Location::Bearer => self.scan_bearer(authorization_value(headers, "bearer").as_deref()),
+        };
+
+        self.build(found)
+    }
+
+    /// Extracts the credentials from an `http` crate request.
+    #[cfg(feature = "http-types")]
+    pub fn extract_from_request<B>(
+        &self,
+        request: &http_types::Request<B>,
+    ) -> Result<(Application, Option<User>), ExtractorError> {
+        let path_qs = request
+            .uri()
+            .path_and_query()
+            .map_or("/", http_types::uri::PathAndQuery::as_str);
+        let headers = request
+            .headers()
+            .iter()
+            .filter_map(|(k, v)| v.to_str().ok().map(|v| (k.as_str(), v)));
+
+        self.extract(path_qs, headers)
+    }
+
+    fn scan<I, K, V>(&self, params: I, case_insensitive: bool) -> Found
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let names_match = |name: &str, param: &str| {
+            if case_insensitive {
+                name.eq_ignore_ascii_case(param)
+            } else {
+                name == param
+            }
+        };
+        let mut found = Found::default();
+
+        for (k, v) in params {
+            let (k, v) = (k.as_ref(), v.as_ref());
+            let slot = if names_match(self.user_key.as_str(), k) {
+                &mut found.user_key
+            } else if names_match(self.app_id.as_str(), k) {
+                &mut found.app_id
+            } else if names_match(self.app_key.as_str(), k) {
+                &mut found.app_key
+            } else if names_match(self.access_token.as_str(), k) {
+                &mut found.access_token
+            } else if self.user_id.as_deref().map_or(false, |u| names_match(u, k)) {
+                &mut found.user_id
+            } else {
+                continue;
+            };
+
+            if slot.is_none() && !v.is_empty() {
+                *slot = Some(v.to_string());
+            }
+        }
+
+        found
+    }
+
+    fn scan_basic(&self, credentials: Option<&str>) -> Result<Found, ExtractorError> {
+        use base64::Engine as _;
+
+        let mut found = Found::default();
+        let credentials = match credentials {
+            Some(credentials) => credentials,
+            None => return Ok(found),
+        };
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(credentials)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or(ExtractorError::InvalidAuthorization)?;
+        let (user, password) = decoded
+            .split_once(':')
+            .ok_or(ExtractorError::InvalidAuthorization)?;
+        let non_empty = |s: &str| Some(s.to_string()).filter(|s| !s.is_empty());
+
+        // Keys are sent as the user name, with app keys being sent as the password.
+        match self.version {
+            BackendVersion::UserKey => found.user_key = non_empty(user),
+            BackendVersion::AppId => {
+                found.app_id = non_empty(user);
+                found.app_key = non_empty(password);
+            }
+            BackendVersion::OAuth | BackendVersion::Oidc => (),
+        }
+
+        Ok(found)
+    }
+
+    fn scan_bearer(&self, token: Option<&str>) -> Found {
+        let mut found = Found::default();
+        let token = token.map(ToString::to_string);
+
+        match self.version {
+            BackendVersion::UserKey => found.user_key = token,
+            BackendVersion::AppId => found.app_id = token,
+            BackendVersion::OAuth | BackendVersion::Oidc => found.access_token = token,
+        }
+
+        found
+    }
+
+    fn build(&self, found: Found) -> Result<(Application, Option<User>), ExtractorError> {
+        let missing = |name: &String| ExtractorError::MissingCredentials(name.clone());
+
+        let application = match self.version {
+            BackendVersion::UserKey => found
+                .user_key
+                .map(Application::from_user_key)
+                .ok_or_else(|| missing(&self.user_key))?,
+            BackendVersion::AppId => {
+                let app_id = found.app_id.ok_or_else(|| missing(&self.app_id))?;
+                match found.app_key {
+                    Some(app_key) => Application::from_app_id_and_key(app_id, app_key),
+                    None => Application::from_app_id(app_id),
+                }
+            }
+            BackendVersion::OAuth | BackendVersion::Oidc => found
+                .access_token
+                .map(Application::from_oauth_token)
+                .ok_or_else(|| missing(&self.access_token))?,
+        };
+
+        Ok((application, found.user_id.map(User::from_user_id)))
+    }
+}
+
+// Query string components are percent-encoded and might encode spaces as '+'.
+fn decode_query_component(s: &str) -> Cow<'_, str> {
+    use percent_encoding::percent_decode_str;
+
+    if s.contains('+') {
+        let s = s.replace('+', " ");
+        Cow::Owned(
+            percent_decode_str(s.as_str())
+                .decode_utf8_lossy()
+                .into_owned(),
+        )
+    } else {
+        percent_decode_str(s).decode_utf8_lossy()
+    }
+}
+
+// Finds the credentials of the Authorization header if it uses the given scheme.
+fn authorization_value<I, K, V>(headers: I, scheme: &str) -> Option<String>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    headers
+        .into_iter()
+        .find(|(k, _)| k.as_ref().eq_ignore_ascii_case("authorization"))
+        .and_then(|(_, v)| {
+            let (auth_scheme, credentials) = v.as_ref().trim().split_once(' ')?;
+
+            if auth_scheme.eq_ignore_ascii_case(scheme) {
+                Some(credentials.trim().to_string())
+            } else {
+                None
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn authorization_location_depends_on_backend_version() {
+        assert_eq!(
+            Location::for_backend_version("authorization", BackendVersion::UserKey).unwrap(),
+            Location::Basic
+        );
+        assert_eq!(
+            Location::for_backend_version("authorization", BackendVersion::OAuth).unwrap(),
+            Location::Bearer
+        );
+        assert_eq!(
+            Location::for_backend_version("headers", BackendVersion::Oidc).unwrap(),
+            Location::Headers
+        );
+        assert!(Location::for_backend_version("cookies", BackendVersion::AppId).is_err());
+    }
+
+    const NO_HEADERS: [(&str, &str); 0] = [];
+
+    #[test]
+    fn extract_from_query_string() {
+        let extractor = Extractor::new(BackendVersion::AppId, Location::Query).user_id_name("uid");
+
+        let (app, user) = extractor
+            .extract(
+                "/a?app_key=a+key%21&uid=u1&app_id=an_app&app_id=other",
+                NO_HEADERS,
+            )
+            .unwrap();
+        assert_eq!(app, Application::from_app_id_and_key("an_app", "a key!"));
+        assert_eq!(user, Some(User::from_user_id("u1")));
+
+        let (app, user) = extractor
+            .extract("/a?app_id=an_app&app_key=", NO_HEADERS)
+            .unwrap();
+        assert_eq!(app, Application::from_app_id("an_app"));
+        assert!(user.is_none());
+
+        assert_eq!(
+            extractor.extract("/a?app_key=a_key", [("app_id", "an_app")]),
+            Err(ExtractorError::MissingCredentials("app_id".into()))
+        );
+    }
+
+    #[test]
+    fn extract_from_headers() {
+        let extractor =
+            Extractor::new(BackendVersion::UserKey, Location::Headers).user_key_name("X-User-Key");
+        let headers = [("accept", "*/*"), ("x-user-key", "a_user_key")];
+
+        let (app, _) = extractor.extract("/?user_key=other", headers).unwrap();
+        assert_eq!(app, Application::from_user_key("a_user_key"));
+
+        assert_eq!(
+            extractor.extract("/?X-User-Key=a_user_key", NO_HEADERS),
+            Err(ExtractorError::MissingCredentials("X-User-Key".into()))
+        );
+    }
+
+    #[test]
+    fn extract_from_basic_authorization() {
+        let extractor = Extractor::new(BackendVersion::AppId, Location::Basic);
+        // an_app:a_key
+        let headers = [("Authorization", "Basic YW5fYXBwOmFfa2V5")];
+
+        let (app, _) = extractor.extract("/", headers).unwrap();
+        assert_eq!(app, Application::from_app_id_and_key("an_app", "a_key"));
+
+        let extractor = Extractor::new(BackendVersion::UserKey, Location::Basic);
+        // a_user_key:
+        let headers = [("authorization", "basic YV91c2VyX2tleTo=")];
+
+        let (app, _) = extractor.extract("/", headers).unwrap();
+        assert_eq!(app, Application::from_user_key("a_user_key"));
+
+        assert_eq!(
+            extractor.extract("/", [("Authorization", "Basic not*base64")]),
+            Err(ExtractorError::InvalidAuthorization)
+        );
+        assert_eq!(
+            extractor.extract("/", [("Authorization", "Bearer YV91c2VyX2tleTo=")]),
+            Err(ExtractorError::MissingCredentials("user_key".into()))
+        );
+    }
+
+    #[test]
+    fn extract_from_bearer_authorization() {
+        let extractor = Extractor::new(BackendVersion::OAuth, Location::Bearer);
+
+        let (app, _) = extractor
+            .extract("/", [("Authorization", "Bearer a_token")])
+            .unwrap();
+        assert_eq!(app, Application::from_oauth_token("a_token"));
+
+        assert_eq!(
+            extractor.extract("/?access_token=a_token", NO_HEADERS),
+            Err(ExtractorError::MissingCredentials("access_token".into()))
+        );
+    }
+
+    #[cfg(feature = "http-types")]
+    #[test]
+    fn extract_from_http_request() {
+        let extractor = Extractor::new(BackendVersion::UserKey, Location::Query);
+        let request = http_types::Request::get("https://example.com/a?user_key=a_user_key")
+            .body(())
+            .unwrap();
+
+        let (app, _) = extractor.extract_from_request(&request).unwrap();
+        assert_eq!(app, Application::from_user_key("a_user_key"));
+    }
+
+    #[test]
+    fn backend_versions_roundtrip() {
+        for version in [
+            BackendVersion::UserKey,
+            BackendVersion::AppId,
+            BackendVersion::OAuth,
+            BackendVersion::Oidc,
+        ] {
+            assert_eq!(version.as_str().parse::<BackendVersion>().unwrap(), version);
+        }
+    }
+}
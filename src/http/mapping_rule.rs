@@ -15,6 +15,27 @@ mod method;
 pub use method::Method;
 
 mod escaping;
+pub use escaping::Error as EscapingError;
+
+mod rule_set;
+pub use rule_set::{MappingRule, RuleSet};
+
+mod analysis;
+pub use analysis::Finding;
+
+mod explain;
+pub use explain::{Explanation, ParamMatch};
+
+mod normalize;
+pub use normalize::{Normalization, Plus};
+
+#[cfg(feature = "openapi")]
+pub mod openapi;
+
+#[cfg(feature = "grpc-mappings")]
+mod grpc;
+#[cfg(feature = "grpc-mappings")]
+pub use grpc::{GrpcRule, GrpcRuleSet};
 
 #[derive(Debug)]
 pub enum HttpLineError {
@@ -26,6 +47,8 @@ pub struct RestRule {
     method: Method,
     path: Regex,
     qs: Option<Vec<Regex>>,
+    source: String,
+    normalization: Normalization,
 }
 
 impl RestRule {
@@ -43,18 +66,45 @@ impl RestRule {
         path: S,
         qs: Option<S>,
     ) -> Result<Self, escaping::Error> {
-        let path = escaping::path_regex(path.as_ref())?;
+        let mut source = path.as_ref().to_string();
+        if let Some(qs) = qs.as_ref() {
+            source.push('?');
+            source.push_str(qs.as_ref());
+        }
+
+        Self::compile(method.into(), source, Normalization::default())
+    }
+
+    /// Sets how both the pattern of this rule and the requests matched against it are
+    /// normalized. This recompiles the rule.
+    pub fn with_normalization(self, normalization: Normalization) -> Result<Self, escaping::Error> {
+        Self::compile(self.method, self.source, normalization)
+    }
+
+    fn compile(
+        method: Method,
+        source: String,
+        normalization: Normalization,
+    ) -> Result<Self, escaping::Error> {
+        let (path, qs) = escaping::split_path_n_qs(source.as_str());
+        let path = escaping::path_regex(normalization.path_pattern(path).as_str())?;
         let qs = qs
-            .map(|qs| escaping::query_string_regex(qs.as_ref()))
+            .map(|qs| escaping::query_string_regex(normalization.query_string_pattern(qs).as_str()))
             .transpose()?;
 
         Ok(Self {
-            method: method.into(),
+            method,
             path,
             qs,
+            source,
+            normalization,
         })
     }
 
+    pub fn normalization(&self) -> &Normalization {
+        &self.normalization
+    }
+
     pub fn matches<S: AsRef<str>>(&self, method: &Method, path_qs: S) -> bool {
         method == &self.method && self.matches_path_with_qs(path_qs)
     }
@@ -72,11 +122,10 @@ impl RestRule {
 
     pub fn matches_path_n_qs<S: AsRef<str>>(&self, path: S, qs: Option<S>) -> bool {
         self.qs.as_deref().map_or(true, |qs_regexes| {
-            let mut kvs = qs
-                .as_ref()
-                .map_or("", AsRef::as_ref)
-                .split('&')
-                .collect::<Vec<_>>();
+            let qs = self
+                .normalization
+                .query_string(qs.as_ref().map_or("", AsRef::as_ref));
+            let mut kvs = qs.split('&').collect::<Vec<_>>();
 
             qs_regexes.iter().all(|regex| {
                 kvs.iter()
@@ -97,7 +146,18 @@ impl RestRule {
             })
         }) && self
             .path
-            .is_match(escaping::coalesce_chars(path.as_ref(), '/').as_str())
+            .is_match(self.normalization.path(path.as_ref()).as_str())
+    }
+
+    /// Matches a request from the `http` crate, using the path and query string of its URI.
+    #[cfg(feature = "http-types")]
+    pub fn matches_http_request<B>(&self, request: &http_types::Request<B>) -> bool {
+        let path_qs = request
+            .uri()
+            .path_and_query()
+            .map_or("/", http_types::uri::PathAndQuery::as_str);
+
+        self.matches(&Method::from(request.method()), path_qs)
     }
 
     pub fn matches_path_with_qs<S: AsRef<str>>(&self, path_qs: S) -> bool {
@@ -110,29 +170,36 @@ impl RestRule {
         &self.method
     }
 
-    // String form of the path + query string pattern
+    /// The path + query string pattern exactly as the rule was created with.
+    pub fn source(&self) -> &str {
+        self.source.as_str()
+    }
+
+    /// String form of the path + query string pattern, with duplicated forward slashes in the
+    /// path coalesced as they are when matching.
     pub fn pattern(&self) -> String {
-        let mut pattern = self.path.as_str()[2..].replace(escaping::PATH_VALUE_REGEX_S, "{_}");
-
-        if let Some(qs) = self.qs.as_deref() {
-            let mut qs = qs
-                .iter()
-                .map(|kv| kv.as_str().replace(escaping::QS_VALUE_REGEX_S, "{_}"));
-
-            if let Some(first) = qs.next() {
-                pattern.push('?');
-                pattern.push_str(&first.as_str()[2..]);
-
-                pattern = qs.fold(pattern, |mut acc, re| {
-                    acc.push('&');
-                    acc.push_str(&re.as_str()[2..]);
-                    acc
-                });
-            }
+        let (path, qs) = escaping::split_path_n_qs(self.source.as_str());
+        let mut pattern = escaping::coalesce_chars(path, '/');
+
+        if let Some(qs) = qs {
+            pattern.push('?');
+            pattern.push_str(qs);
         }
 
         pattern
     }
+
+    /// The names of the placeholders in the pattern, in order of appearance.
+    pub fn placeholders(&self) -> Vec<&str> {
+        escaping::placeholder_names(self.source.as_str()).collect()
+    }
+
+    // String form of each of the query string parameter patterns, in the order of the regexes.
+    fn qs_param_patterns(&self) -> Vec<&str> {
+        escaping::split_path_n_qs(self.source.as_str())
+            .1
+            .map_or_else(Vec::new, escaping::split_qs_params)
+    }
 }
 
 #[cfg(test)]
@@ -294,4 +361,48 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn match_normalized_requests() -> Result<(), escaping::Error> {
+        let mr = RestRule::new(Method::GET, "/%61dmin/{id}$?q=a+b")?;
+
+        assert!(mr.matches(&Method::GET, "/admin/1?q=a+b"));
+        assert!(mr.matches(&Method::GET, "/public/../%61dmin/./1?q=%61+b"));
+        assert!(!mr.matches(&Method::GET, "/admin%2F1?q=a+b"));
+        assert!(!mr.matches(&Method::GET, "/admin/1?q=a%20b"));
+
+        let mr =
+            mr.with_normalization(Normalization::new().decode_slashes(true).plus(Plus::Space))?;
+        assert!(mr.matches(&Method::GET, "/admin%2F1?q=a%20b"));
+
+        let mr = mr.with_normalization(Normalization::none())?;
+        assert!(mr.matches(&Method::GET, "/%61dmin/1?q=a+b"));
+        assert!(!mr.matches(&Method::GET, "/admin/1?q=a+b"));
+        assert!(!mr.matches(&Method::GET, "/x/../%61dmin/1?q=a+b"));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "http-types")]
+    #[test]
+    fn match_http_requests() -> Result<(), escaping::Error> {
+        let mr = RestRule::new(Method::POST, "/products/{id}?action=buy")?;
+        let request = |method: &str, uri: &str| {
+            http_types::Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(())
+                .unwrap()
+        };
+
+        assert!(mr.matches_http_request(&request(
+            "POST",
+            "https://example.com/products/1?action=buy"
+        )));
+        assert!(mr.matches_http_request(&request("POST", "/products/1?action=buy&q=1")));
+        assert!(!mr.matches_http_request(&request("GET", "/products/1?action=buy")));
+        assert!(!mr.matches_http_request(&request("POST", "/products/1")));
+
+        Ok(())
+    }
 }
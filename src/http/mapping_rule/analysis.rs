@@ -0,0 +1,578 @@
+// Static analysis of mapping rules.
+//
+// Patterns are compiled into regular expressions with a very restricted shape: literal text,
+// placeholders that take one or more characters of a fixed class, and an optional end anchor.
+// This module recovers that structure from the compiled expressions and reasons on it as a
+// small automaton, which is enough to decide whether two rules can match the same request or
+// whether every request matching a rule also matches another one.
+//
+// Literal text in path patterns is not escaped, so patterns can contain arbitrary regular
+// expression constructs. The any character operator `.` is commonly found (ie. in file
+// extensions) and supported, but rules using any other operator cannot be analyzed.
+use std::prelude::v1::*;
+
+use core::fmt;
+use std::collections::BTreeSet;
+
+use super::{escaping, Method, RestRule, RuleSet};
+
+/// A problem found when analyzing a set of mapping rules.
+///
+/// Rules are referred to by their index in the set.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Finding {
+    /// Both rules match exactly the same requests, so they are always counted together.
+    Identical { first: usize, second: usize },
+    /// Some requests match both rules, and so they are counted together.
+    Overlapping { first: usize, second: usize },
+    /// Some requests matching the rule are not counted because an earlier `last` rule matches.
+    Shadowed { rule: usize, by: usize },
+    /// Every request matching the rule matches an earlier `last` rule, so it is never counted.
+    Unreachable { rule: usize, by: usize },
+    /// The pattern of the rule uses regular expression operators that cannot be analyzed.
+    Unsupported { rule: usize },
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Identical { first, second } => {
+                write!(f, "rules #{} and #{} are identical", first, second)
+            }
+            Self::Overlapping { first, second } => {
+                write!(f, "rules #{} and #{} overlap", first, second)
+            }
+            Self::Shadowed { rule, by } => {
+                write!(
+                    f,
+                    "rule #{} is partially shadowed by last rule #{}",
+                    rule, by
+                )
+            }
+            Self::Unreachable { rule, by } => {
+                write!(
+                    f,
+                    "rule #{} is unreachable because of last rule #{}",
+                    rule, by
+                )
+            }
+            Self::Unsupported { rule } => {
+                write!(f, "rule #{} uses a pattern that cannot be analyzed", rule)
+            }
+        }
+    }
+}
+
+impl RestRule {
+    /// Whether there are requests matching both rules.
+    ///
+    /// Returns `None` if either pattern cannot be analyzed.
+    pub fn overlaps(&self, other: &RestRule) -> Option<bool> {
+        let (this, other_structure) = (Structure::of(self)?, Structure::of(other)?);
+
+        // Query string requirements of both rules can always be satisfied at once by adding
+        // the parameters required by each of them, so only methods and paths matter.
+        Some(self.method == other.method && this.path.intersects(&other_structure.path))
+    }
+
+    /// Whether every request matching `other` also matches this rule.
+    ///
+    /// Returns `None` if either pattern cannot be analyzed. A rule covering another one through
+    /// the combination of several of its query string parameters might not be detected.
+    pub fn covers(&self, other: &RestRule) -> Option<bool> {
+        let (this, other_structure) = (Structure::of(self)?, Structure::of(other)?);
+
+        Some(
+            method_covers(&self.method, &other.method)
+                && other_structure.path.is_subset(&this.path)
+                && qs_covers(&this.qs, &other_structure.qs),
+        )
+    }
+}
+
+impl RuleSet {
+    /// Analyzes the rules looking for overlapping, identical, shadowed and unreachable ones.
+    ///
+    /// Each pair of rules is considered on its own, so a rule only made unreachable by the
+    /// combination of several earlier `last` rules is reported as shadowed by each of them.
+    pub fn analyze(&self) -> Vec<Finding> {
+        let rules = self.as_slice();
+        let structures = rules
+            .iter()
+            .map(|r| Structure::of(r.rule()))
+            .collect::<Vec<_>>();
+        let mut unreachable = vec![false; rules.len()];
+        let mut findings = Vec::new();
+
+        for (idx, rule) in rules.iter().enumerate() {
+            let structure = match &structures[idx] {
+                Some(structure) => structure,
+                None => {
+                    findings.push(Finding::Unsupported { rule: idx });
+                    continue;
+                }
+            };
+            let mut rule_findings = Vec::new();
+
+            for (prev_idx, prev) in rules[..idx].iter().enumerate() {
+                // rules that never get counted cannot overlap with others
+                let prev_structure = match &structures[prev_idx] {
+                    Some(prev_structure) if !unreachable[prev_idx] => prev_structure,
+                    _ => continue,
+                };
+
+                let (prev_rule, current) = (prev.rule(), rule.rule());
+                let overlap = prev_rule.method == current.method
+                    && prev_structure.path.intersects(&structure.path);
+                if !overlap {
+                    continue;
+                }
+
+                let covered = method_covers(&prev_rule.method, &current.method)
+                    && structure.path.is_subset(&prev_structure.path)
+                    && qs_covers(&prev_structure.qs, &structure.qs);
+
+                if prev.is_last() {
+                    if covered {
+                        // the rule never gets counted, so anything else about it is moot
+                        rule_findings.clear();
+                        unreachable[idx] = true;
+                        rule_findings.push(Finding::Unreachable {
+                            rule: idx,
+                            by: prev_idx,
+                        });
+                        break;
+                    }
+                    rule_findings.push(Finding::Shadowed {
+                        rule: idx,
+                        by: prev_idx,
+                    });
+                } else if covered
+                    && method_covers(&current.method, &prev_rule.method)
+                    && prev_structure.path.is_subset(&structure.path)
+                    && qs_covers(&structure.qs, &prev_structure.qs)
+                {
+                    rule_findings.push(Finding::Identical {
+                        first: prev_idx,
+                        second: idx,
+                    });
+                } else {
+                    rule_findings.push(Finding::Overlapping {
+                        first: prev_idx,
+                        second: idx,
+                    });
+                }
+            }
+
+            findings.append(&mut rule_findings);
+        }
+
+        findings
+    }
+}
+
+// Method::Any compares equal to every method, so containment has to look at the variants.
+fn method_covers(method: &Method, other: &Method) -> bool {
+    match (method, other) {
+        (Method::Any, _) => true,
+        (_, Method::Any) => false,
+        _ => method == other,
+    }
+}
+
+// Every parameter required by the covering rule has to be implied by a different parameter
+// required by the covered one. Parameters are assigned greedily.
+fn qs_covers(qs: &[Pattern], other: &[Pattern]) -> bool {
+    let mut available = other.iter().collect::<Vec<_>>();
+
+    qs.iter().all(|kv| {
+        available
+            .iter()
+            .position(|other_kv| other_kv.is_subset(kv))
+            .map(|idx| available.remove(idx))
+            .is_some()
+    })
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    AnyChar,
+    Placeholder,
+    End,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Kind {
+    Path,
+    QueryString,
+}
+
+impl Kind {
+    fn placeholder_value(self) -> &'static str {
+        match self {
+            Self::Path => escaping::PATH_VALUE_REGEX_S,
+            Self::QueryString => escaping::QS_VALUE_REGEX_S,
+        }
+    }
+
+    // Mirrors the character classes of the placeholder regexes.
+    fn is_placeholder_char(self, c: char) -> bool {
+        c.is_ascii_alphanumeric()
+            || match self {
+                Self::Path => "_-.~%!$&'()*+,;=@:".contains(c),
+                Self::QueryString => "_-.~%!$'()*+,;=@:".contains(c),
+            }
+    }
+
+    // Characters that never show up in the strings matched against the patterns.
+    fn is_excluded(self, c: char) -> bool {
+        match self {
+            Self::Path => c == '?',
+            Self::QueryString => c == '&',
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Pattern {
+    kind: Kind,
+    tokens: Vec<Token>,
+}
+
+struct Structure {
+    path: Pattern,
+    qs: Vec<Pattern>,
+}
+
+impl Structure {
+    fn of(rule: &RestRule) -> Option<Self> {
+        let path = Pattern::parse(rule.path.as_str(), Kind::Path)?;
+        let qs = rule
+            .qs
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .map(|kv| Pattern::parse(kv.as_str(), Kind::QueryString))
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self { path, qs })
+    }
+}
+
+// A state of the automaton is the index of the next token to match. The state past the last
+// token accepts anything that follows, since patterns only anchor to the start, whereas an
+// end anchor token only accepts if nothing follows.
+impl Pattern {
+    fn parse(regex: &str, kind: Kind) -> Option<Self> {
+        let regex = regex.strip_prefix(escaping::START_RE)?;
+        let mut tokens = Vec::with_capacity(regex.len());
+
+        for (idx, literal) in regex.split(kind.placeholder_value()).enumerate() {
+            if idx > 0 {
+                tokens.push(Token::Placeholder);
+            }
+
+            let mut chars = literal.chars();
+            while let Some(c) = chars.next() {
+                let token = match c {
+                    '\\' => match chars.next()? {
+                        c if c.is_ascii_punctuation() || c == ' ' => Token::Char(c),
+                        _ => return None,
+                    },
+                    '.' => Token::AnyChar,
+                    '$' => Token::End,
+                    '^' | '*' | '+' | '?' | '(' | ')' | '[' | ']' | '{' | '}' | '|' => return None,
+                    c => Token::Char(c),
+                };
+                tokens.push(token);
+            }
+        }
+
+        Some(Self { kind, tokens })
+    }
+
+    fn accepts(&self, state: usize) -> bool {
+        state == self.tokens.len() || self.tokens[state] == Token::End
+    }
+
+    fn step(&self, state: usize, c: char, next: &mut BTreeSet<usize>) {
+        match self.tokens.get(state) {
+            None => {
+                next.insert(state);
+            }
+            Some(Token::Char(l)) if *l == c => {
+                next.insert(state + 1);
+            }
+            Some(Token::AnyChar) => {
+                next.insert(state + 1);
+            }
+            Some(Token::Placeholder) if self.kind.is_placeholder_char(c) => {
+                next.insert(state);
+                next.insert(state + 1);
+            }
+            _ => (),
+        }
+    }
+
+    fn literals(&self) -> impl Iterator<Item = char> + '_ {
+        self.tokens.iter().filter_map(|t| match t {
+            Token::Char(c) => Some(*c),
+            _ => None,
+        })
+    }
+
+    // Characters that are not literals in either pattern are only told apart by whether they
+    // can be part of a placeholder, so a representative of each class is enough to explore all
+    // the strings the patterns can match.
+    fn alphabet(&self, other: &Pattern) -> Vec<char> {
+        let mut alphabet = self
+            .literals()
+            .chain(other.literals())
+            .collect::<BTreeSet<_>>();
+        let unused = |c: &char| !alphabet.contains(c) && !self.kind.is_excluded(*c);
+
+        let placeholder_rep = "aZ09_-.~%!$&'()*+,;=@:"
+            .chars()
+            .filter(|c| self.kind.is_placeholder_char(*c))
+            .find(unused);
+        let other_rep = "/# \"<>^`|{}\\[]\u{e9}"
+            .chars()
+            .filter(|c| !self.kind.is_placeholder_char(*c))
+            .find(unused);
+
+        alphabet.extend(placeholder_rep);
+        alphabet.extend(other_rep);
+        alphabet.into_iter().collect()
+    }
+
+    // Paths have their duplicated slashes coalesced before matching.
+    fn allows(&self, after_slash: bool, c: char) -> bool {
+        !(self.kind == Kind::Path && after_slash && c == '/')
+    }
+
+    fn intersects(&self, other: &Pattern) -> bool {
+        let alphabet = self.alphabet(other);
+        let mut seen = BTreeSet::new();
+        let mut pending = vec![(0, 0, false)];
+
+        while let Some((state, other_state, after_slash)) = pending.pop() {
+            if !seen.insert((state, other_state, after_slash)) {
+                continue;
+            }
+            if self.accepts(state) && other.accepts(other_state) {
+                return true;
+            }
+
+            for &c in alphabet.iter().filter(|&&c| self.allows(after_slash, c)) {
+                let (mut next, mut other_next) = (BTreeSet::new(), BTreeSet::new());
+                self.step(state, c, &mut next);
+                other.step(other_state, c, &mut other_next);
+
+                for &n in &next {
+                    for &on in &other_next {
+                        pending.push((n, on, c == '/'));
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    // Explores this automaton against the determinized other one, looking for a string this
+    // pattern matches while the other does not.
+    fn is_subset(&self, other: &Pattern) -> bool {
+        let alphabet = self.alphabet(other);
+        let mut seen = BTreeSet::new();
+        let mut pending = vec![(0, BTreeSet::from([0]), false)];
+
+        while let Some((state, other_states, after_slash)) = pending.pop() {
+            if self.accepts(state) && !other_states.iter().any(|&s| other.accepts(s)) {
+                return false;
+            }
+            if !seen.insert((state, other_states.clone(), after_slash)) {
+                continue;
+            }
+
+            for &c in alphabet.iter().filter(|&&c| self.allows(after_slash, c)) {
+                let mut next = BTreeSet::new();
+                self.step(state, c, &mut next);
+                if next.is_empty() {
+                    continue;
+                }
+
+                let mut other_next = BTreeSet::new();
+                for &s in &other_states {
+                    other.step(s, c, &mut other_next);
+                }
+
+                for n in next {
+                    pending.push((n, other_next.clone(), c == '/'));
+                }
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::mapping_rule::MappingRule;
+
+    fn rule(method: &str, pattern: &str, last: bool) -> MappingRule {
+        MappingRule::new(RestRule::new(method, pattern).unwrap(), "hits", 1, last)
+    }
+
+    fn rest_rule(method: &str, pattern: &str) -> RestRule {
+        RestRule::new(method, pattern).unwrap()
+    }
+
+    #[test]
+    fn placeholder_classes_mirror_regexes() {
+        use regex::Regex;
+
+        for (kind, re) in [
+            (Kind::Path, escaping::PATH_VALUE_REGEX_S),
+            (Kind::QueryString, escaping::QS_VALUE_REGEX_S),
+        ] {
+            let re = Regex::new(&format!("^{}$", re)).unwrap();
+
+            for c in (0u8..128).map(char::from) {
+                assert_eq!(
+                    kind.is_placeholder_char(c),
+                    re.is_match(c.encode_utf8(&mut [0; 4])),
+                    "{:?}",
+                    c
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn overlapping_rules() {
+        let cases = [
+            ("GET", "/a", "GET", "/a/b", true),
+            ("GET", "/a$", "GET", "/a/b", false),
+            ("GET", "/a/{id}$", "GET", "/a/b$", true),
+            ("GET", "/a/{id}$", "GET", "/a/b/c$", false),
+            ("GET", "/a/{id}/c", "GET", "/a/{x}", true),
+            ("GET", "/file.json$", "GET", "/file{ext}$", true),
+            ("GET", "/a$", "POST", "/a$", false),
+            ("ANY", "/a$", "POST", "/a$", true),
+            ("GET", "/a?x=1", "GET", "/a?x=2", true),
+            ("GET", "/{a}{b}$", "GET", "/c$", false),
+        ];
+
+        for (m1, p1, m2, p2, expected) in cases {
+            let (r1, r2) = (rest_rule(m1, p1), rest_rule(m2, p2));
+
+            assert_eq!(
+                r1.overlaps(&r2),
+                Some(expected),
+                "{} {} / {} {}",
+                m1,
+                p1,
+                m2,
+                p2
+            );
+            assert_eq!(
+                r2.overlaps(&r1),
+                Some(expected),
+                "{} {} / {} {}",
+                m2,
+                p2,
+                m1,
+                p1
+            );
+        }
+    }
+
+    #[test]
+    fn covering_rules() {
+        let cases = [
+            ("GET", "/", "GET", "/a", true),
+            ("GET", "/a", "GET", "/", false),
+            ("ANY", "/a", "GET", "/a/b", true),
+            ("GET", "/a", "ANY", "/a/b", false),
+            ("GET", "/a/{id}$", "GET", "/a/b$", true),
+            ("GET", "/a/{id}", "GET", "/a/b.json", true),
+            ("GET", "/a/b.json", "GET", "/a/{id}", false),
+            ("GET", "/a/{id}", "GET", "//a//{x}/{y}", true),
+            ("GET", "/a?x={x}", "GET", "/a?y=1&x=2", true),
+            ("GET", "/a?x={x}&x=1", "GET", "/a?x=2", false),
+            ("GET", "/a", "GET", "/a?x=1", true),
+            ("GET", "/a?x=1", "GET", "/a", false),
+        ];
+
+        for (m1, p1, m2, p2, expected) in cases {
+            assert_eq!(
+                rest_rule(m1, p1).covers(&rest_rule(m2, p2)),
+                Some(expected),
+                "{} {} / {} {}",
+                m1,
+                p1,
+                m2,
+                p2
+            );
+        }
+    }
+
+    #[test]
+    fn unsupported_patterns() {
+        assert_eq!(
+            rest_rule("GET", "/a(b)?").overlaps(&rest_rule("GET", "/")),
+            None
+        );
+        assert_eq!(rest_rule("GET", "/").covers(&rest_rule("GET", "/a+")), None);
+    }
+
+    #[test]
+    fn analyze_rule_set() {
+        let rules = RuleSet::from(vec![
+            rule("ANY", "/", false),
+            rule("GET", "/products/{id}$", true),
+            rule("GET", "/products/1$", false),
+            rule("GET", "/products/{id}", false),
+            rule("POST", "/orders", false),
+            rule("POST", "//orders", false),
+            rule("GET", "/a[bc]", false),
+        ]);
+
+        assert_eq!(
+            rules.analyze(),
+            vec![
+                Finding::Overlapping {
+                    first: 0,
+                    second: 1
+                },
+                Finding::Unreachable { rule: 2, by: 1 },
+                Finding::Overlapping {
+                    first: 0,
+                    second: 3
+                },
+                Finding::Shadowed { rule: 3, by: 1 },
+                Finding::Overlapping {
+                    first: 0,
+                    second: 4
+                },
+                Finding::Overlapping {
+                    first: 0,
+                    second: 5
+                },
+                Finding::Identical {
+                    first: 4,
+                    second: 5
+                },
+                Finding::Unsupported { rule: 6 },
+            ]
+        );
+        assert_eq!(
+            Finding::Unreachable { rule: 2, by: 1 }.to_string(),
+            "rule #2 is unreachable because of last rule #1"
+        );
+    }
+}
@@ -10,6 +10,25 @@ pub enum Error {
     RegexTooBig,
 }
 
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::RegexError(e) => write!(f, "regex error: {}", e),
+            Self::RegexTooBig => f.write_str("regex requires too much memory"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::RegexError(e) => Some(e),
+            Self::RegexTooBig => None,
+        }
+    }
+}
+
 impl From<regex::Error> for Error {
     fn from(e: regex::Error) -> Self {
         Self::RegexError(e)
@@ -103,6 +122,50 @@ pub(super) fn path_regex(path: &str) -> Result<Regex, Error> {
     Ok(Regex::new(final_regex.as_str())?)
 }
 
+// Transforms the text in between placeholders, keeping these as they are.
+pub(super) fn map_literals<F: FnMut(&str) -> String>(s: &str, mut f: F) -> String {
+    let mut mapped = String::with_capacity(s.len());
+    let mut start = 0;
+
+    for m in PLACEHOLDER_REGEX.find_iter(s) {
+        mapped.push_str(f(&s[start..m.start()]).as_str());
+        mapped.push_str(m.as_str());
+        start = m.end();
+    }
+    mapped.push_str(f(&s[start..]).as_str());
+
+    mapped
+}
+
+// Names of the placeholders in a pattern, without the surrounding braces.
+pub(super) fn placeholder_names(s: &str) -> impl Iterator<Item = &str> {
+    PLACEHOLDER_REGEX
+        .find_iter(s)
+        .map(|m| &m.as_str()[1..m.as_str().len() - 1])
+}
+
+// Splits a query string pattern into its parameters the same way `query_string_regex` does,
+// that is, ignoring ampersands within placeholders.
+pub(super) fn split_qs_params(s: &str) -> Vec<&str> {
+    let placeholders = PLACEHOLDER_REGEX
+        .find_iter(s)
+        .map(|m| m.range())
+        .collect::<Vec<_>>();
+    let mut params = Vec::new();
+    let mut start = 0;
+
+    for (idx, _) in s
+        .match_indices('&')
+        .filter(|(idx, _)| !placeholders.iter().any(|range| range.contains(idx)))
+    {
+        params.push(&s[start..idx]);
+        start = idx + 1;
+    }
+    params.push(&s[start..]);
+
+    params
+}
+
 pub(super) fn split_path_n_qs(s: &str) -> (&str, Option<&str>) {
     s.find('?')
         .map_or((s, None), |idx| (&s[..idx], Some(&s[idx + 1..])))
@@ -166,6 +229,21 @@ mod test {
     mod query_string_regex {
         use super::*;
 
+        #[test]
+        fn splits_params_like_regexes() -> Result<(), Error> {
+            let qs_patterns = "fmt={fmt}&&lang{a&b}={lang}&";
+            let params = split_qs_params(qs_patterns);
+
+            assert_eq!(params, vec!["fmt={fmt}", "", "lang{a&b}={lang}", ""]);
+            assert_eq!(params.len(), query_string_regex(qs_patterns)?.len());
+            assert_eq!(
+                placeholder_names(qs_patterns).collect::<Vec<_>>(),
+                vec!["fmt", "a&b", "lang"]
+            );
+
+            Ok(())
+        }
+
         #[test]
         fn builds_correct_qs_param_regexes() -> Result<(), Error> {
             let qs_patterns = "fmt={fmt}&hardcoded=1&lang{num}={lang}";
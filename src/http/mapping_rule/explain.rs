@@ -0,0 +1,190 @@
+use std::prelude::v1::*;
+
+use super::{escaping, Method, RestRule};
+
+/// Outcome of matching one of the query string parameters required by a rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParamMatch {
+    pattern: String,
+    matched: Option<String>,
+}
+
+impl ParamMatch {
+    /// The pattern of the required parameter, as in `RestRule::pattern`.
+    pub fn pattern(&self) -> &str {
+        self.pattern.as_str()
+    }
+
+    /// The parameter of the request that satisfied the requirement, if any.
+    pub fn matched(&self) -> Option<&str> {
+        self.matched.as_deref()
+    }
+
+    pub fn is_match(&self) -> bool {
+        self.matched.is_some()
+    }
+}
+
+/// A trace of how a request was matched against a `RestRule`.
+///
+/// Every step is evaluated, even after one of them fails, so that all the reasons for a request
+/// not matching are reported at once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    rule_method: Method,
+    method: Method,
+    method_matched: bool,
+    pattern: String,
+    path: String,
+    path_matched: bool,
+    params: Vec<ParamMatch>,
+}
+
+impl Explanation {
+    /// Whether the request matches the rule.
+    pub fn is_match(&self) -> bool {
+        self.method_matched && self.path_matched && self.params.iter().all(ParamMatch::is_match)
+    }
+
+    /// The method of the rule.
+    pub fn rule_method(&self) -> &Method {
+        &self.rule_method
+    }
+
+    /// The method of the request.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    pub fn method_matched(&self) -> bool {
+        self.method_matched
+    }
+
+    /// The pattern of the rule, as in `RestRule::pattern`.
+    pub fn pattern(&self) -> &str {
+        self.pattern.as_str()
+    }
+
+    /// The path of the request after normalizing it, which is what the path pattern is matched
+    /// against.
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    pub fn path_matched(&self) -> bool {
+        self.path_matched
+    }
+
+    /// The outcome of each query string parameter required by the rule, in the rule's order.
+    pub fn params(&self) -> &[ParamMatch] {
+        self.params.as_slice()
+    }
+}
+
+impl RestRule {
+    /// Matches a request against this rule, recording the outcome of each step.
+    ///
+    /// The result of `Explanation::is_match` is always the same as that of `RestRule::matches`.
+    pub fn explain<S: AsRef<str>>(&self, method: &Method, path_qs: S) -> Explanation {
+        let (path, qs) = escaping::split_path_n_qs(path_qs.as_ref());
+        let path = self.normalization.path(path);
+        let path_matched = self.path.is_match(path.as_str());
+
+        // Parameters of the request are assigned to the required ones in the same way as when
+        // matching, so that a parameter satisfies at most one requirement.
+        let qs = self.normalization.query_string(qs.unwrap_or(""));
+        let mut kvs = qs.split('&').collect::<Vec<_>>();
+        let params = self
+            .qs
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .zip(self.qs_param_patterns())
+            .map(|(regex, pattern)| {
+                let matched = kvs
+                    .iter()
+                    .position(|kv| regex.is_match(kv))
+                    .map(|idx| kvs.remove(idx).to_string());
+
+                ParamMatch {
+                    pattern: pattern.to_string(),
+                    matched,
+                }
+            })
+            .collect();
+
+        Explanation {
+            rule_method: self.method.clone(),
+            method: method.clone(),
+            method_matched: method == &self.method,
+            pattern: self.pattern(),
+            path,
+            path_matched,
+            params,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explain_match() {
+        let rule = RestRule::new("GET", "/products/{id}$?fmt={fmt}&v=2").unwrap();
+        let explanation = rule.explain(&Method::GET, "//products//7?v=2&fmt=json&other");
+
+        assert!(explanation.is_match());
+        assert!(explanation.method_matched());
+        assert_eq!(explanation.path(), "/products/7");
+        assert!(explanation.path_matched());
+        assert_eq!(
+            explanation
+                .params()
+                .iter()
+                .map(|p| (p.pattern(), p.matched()))
+                .collect::<Vec<_>>(),
+            vec![("fmt={fmt}", Some("fmt=json")), ("v=2", Some("v=2"))]
+        );
+    }
+
+    #[test]
+    fn explain_every_failure() {
+        let rule = RestRule::new("POST", "/products$?fmt={fmt}&v=2").unwrap();
+        let explanation = rule.explain(&Method::GET, "/products/?v=3&fmt=");
+
+        assert!(!explanation.is_match());
+        assert!(!explanation.method_matched());
+        assert_eq!(explanation.rule_method(), &Method::POST);
+        assert_eq!(explanation.path(), "/products/");
+        assert!(!explanation.path_matched());
+        assert!(explanation.params().iter().all(|p| !p.is_match()));
+
+        let explanation = rule.explain(&Method::POST, "/products");
+        assert!(explanation.path_matched());
+        assert_eq!(explanation.params().len(), 2);
+        assert!(!explanation.is_match());
+    }
+
+    #[test]
+    fn explanation_agrees_with_matching() {
+        let rule = RestRule::new("ANY", "/auto?maybe_empty=&w=hello&color={color}").unwrap();
+        let requests = [
+            "/",
+            "/auto?",
+            "/auto?w=hello&color=red&maybe_empty",
+            "/auto?w=hello&color=red&maybe_empty=",
+            "/auto-matic?color=black&w=hello&maybe_empty=&",
+            "/auto-matic?w=hello&color&maybe_empty=",
+        ];
+
+        for request in requests {
+            assert_eq!(
+                rule.explain(&Method::PUT, request).is_match(),
+                rule.matches(&Method::PUT, request),
+                "{}",
+                request
+            );
+        }
+    }
+}
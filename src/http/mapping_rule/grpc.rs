@@ -0,0 +1,392 @@
+// gRPC mapping rules.
+//
+// gRPC calls are HTTP/2 POST requests to `/<package>.<Service>/<Method>`, so rather than
+// matching paths these rules match the fully-qualified service name and the method name. Both
+// can contain `*` wildcards matching any sequence of characters.
+use std::prelude::v1::*;
+
+use std::{iter::FromIterator, slice::Iter, vec::IntoIter};
+
+use prost::Message;
+use prost_types::FileDescriptorSet;
+
+use super::{rule_set::add_up_usage, Method};
+use crate::{Error, ParseError};
+
+/// A rule matching gRPC calls bound to the metric it increments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrpcRule {
+    service: String,
+    method: String,
+    metric: String,
+    delta: u64,
+    last: bool,
+}
+
+impl GrpcRule {
+    pub fn new<S, N, M>(service: S, method: N, metric: M, delta: u64, last: bool) -> Self
+    where
+        S: Into<String>,
+        N: Into<String>,
+        M: Into<String>,
+    {
+        Self {
+            service: service.into(),
+            method: method.into(),
+            metric: metric.into(),
+            delta,
+            last,
+        }
+    }
+
+    /// The pattern for the fully-qualified service name, ie. `helloworld.Greeter`.
+    pub fn service(&self) -> &str {
+        self.service.as_str()
+    }
+
+    /// The pattern for the method name.
+    pub fn method(&self) -> &str {
+        self.method.as_str()
+    }
+
+    pub fn metric(&self) -> &str {
+        self.metric.as_str()
+    }
+
+    pub fn delta(&self) -> u64 {
+        self.delta
+    }
+
+    /// Whether matching this rule stops the evaluation of the rules that follow it.
+    pub fn is_last(&self) -> bool {
+        self.last
+    }
+
+    pub fn matches(&self, service: &str, method: &str) -> bool {
+        wildcard_match(self.service.as_str(), service)
+            && wildcard_match(self.method.as_str(), method)
+    }
+
+    /// Matches the path of a gRPC request, ie. `/helloworld.Greeter/SayHello`.
+    pub fn matches_path<S: AsRef<str>>(&self, path: S) -> bool {
+        parse_path(path.as_ref()).map_or(false, |(service, method)| self.matches(service, method))
+    }
+
+    /// Matches a request, which can only be a gRPC call if its method is POST.
+    pub fn matches_request<S: AsRef<str>>(&self, method: &Method, path: S) -> bool {
+        matches!(method, Method::POST) && self.matches_path(path)
+    }
+}
+
+// Splits the path of a gRPC request into the fully-qualified service name and the method name.
+fn parse_path(path: &str) -> Option<(&str, &str)> {
+    let (service, method) = path.strip_prefix('/')?.split_once('/')?;
+
+    if service.is_empty() || method.is_empty() || method.contains('/') {
+        None
+    } else {
+        Some((service, method))
+    }
+}
+
+// Matches a pattern where `*` stands for any sequence of characters, backtracking only to the
+// last wildcard seen.
+fn wildcard_match(pattern: &str, s: &str) -> bool {
+    let (pattern, s) = (pattern.as_bytes(), s.as_bytes());
+    let (mut p, mut i) = (0, 0);
+    let mut backtrack = None;
+
+    while i < s.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, i));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == s[i] {
+            p += 1;
+            i += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            i = matched + 1;
+            backtrack = Some((star, i));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// An ordered set of gRPC mapping rules, evaluated like a `RuleSet`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GrpcRuleSet(Vec<GrpcRule>);
+
+impl GrpcRuleSet {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Builds a rule per method of each service in an encoded `FileDescriptorSet`, as produced
+    /// by `protoc --descriptor_set_out`. Metrics are named after the fully-qualified method with
+    /// dots replaced by underscores, ie. `helloworld_Greeter_SayHello`.
+    pub fn from_descriptor_set(bytes: &[u8]) -> Result<Self, Error> {
+        Self::from_descriptor_set_with(bytes, |service, method| {
+            [service, method]
+                .join(".")
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect()
+        })
+    }
+
+    /// Like `from_descriptor_set`, but naming metrics with the given function, which is passed
+    /// the fully-qualified service name and the method name.
+    pub fn from_descriptor_set_with<F>(bytes: &[u8], mut metric: F) -> Result<Self, Error>
+    where
+        F: FnMut(&str, &str) -> String,
+    {
+        let set = FileDescriptorSet::decode(bytes).map_err(|e| {
+            ParseError::new("failed to decode the file descriptor set").with_source(e)
+        })?;
+        let mut rules = Self::new();
+
+        for file in set.file.iter() {
+            for service in file.service.iter() {
+                let service_name = match (file.package(), service.name()) {
+                    (_, "") => {
+                        return Err(Error::Parse(ParseError::new(
+                            "found a service without a name",
+                        )))
+                    }
+                    ("", name) => name.to_string(),
+                    (package, name) => [package, name].join("."),
+                };
+
+                for method in service.method.iter().map(|m| m.name()) {
+                    let metric = metric(service_name.as_str(), method);
+                    rules.push(GrpcRule::new(
+                        service_name.as_str(),
+                        method,
+                        metric,
+                        1,
+                        false,
+                    ));
+                }
+            }
+        }
+
+        Ok(rules)
+    }
+
+    pub fn push(&mut self, rule: GrpcRule) {
+        self.0.push(rule);
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> Iter<'_, GrpcRule> {
+        self.0.iter()
+    }
+
+    pub fn as_slice(&self) -> &[GrpcRule] {
+        self.0.as_slice()
+    }
+
+    /// Returns the rules matching a call in evaluation order, stopping after the first matching
+    /// rule marked as `last`.
+    pub fn matching(&self, service: &str, method: &str) -> Vec<&GrpcRule> {
+        let mut matched = Vec::new();
+
+        for rule in self.0.iter().filter(|r| r.matches(service, method)) {
+            matched.push(rule);
+            if rule.is_last() {
+                break;
+            }
+        }
+
+        matched
+    }
+
+    /// Computes the usage a call should report, adding up the deltas of all matching rules per
+    /// metric. Metrics are listed in the order they were first matched.
+    pub fn usage(&self, service: &str, method: &str) -> Vec<(&str, u64)> {
+        add_up_usage(
+            self.matching(service, method)
+                .into_iter()
+                .map(|rule| (rule.metric(), rule.delta())),
+        )
+    }
+
+    /// Computes the usage of a request, which is empty if it is not a gRPC call.
+    pub fn usage_for_request<S: AsRef<str>>(&self, method: &Method, path: S) -> Vec<(&str, u64)> {
+        match parse_path(path.as_ref()) {
+            Some((service, grpc_method)) if matches!(method, Method::POST) => {
+                self.usage(service, grpc_method)
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl From<Vec<GrpcRule>> for GrpcRuleSet {
+    fn from(rules: Vec<GrpcRule>) -> Self {
+        Self(rules)
+    }
+}
+
+impl FromIterator<GrpcRule> for GrpcRuleSet {
+    fn from_iter<T: IntoIterator<Item = GrpcRule>>(iter: T) -> Self {
+        Self(Vec::from_iter(iter))
+    }
+}
+
+impl Extend<GrpcRule> for GrpcRuleSet {
+    fn extend<T: IntoIterator<Item = GrpcRule>>(&mut self, iter: T) {
+        self.0.extend(iter);
+    }
+}
+
+impl<'r> IntoIterator for &'r GrpcRuleSet {
+    type IntoIter = Iter<'r, GrpcRule>;
+    type Item = &'r GrpcRule;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl IntoIterator for GrpcRuleSet {
+    type IntoIter = IntoIter<GrpcRule>;
+    type Item = GrpcRule;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_types::{FileDescriptorProto, MethodDescriptorProto, ServiceDescriptorProto};
+
+    #[test]
+    fn wildcards() {
+        let cases = [
+            ("helloworld.Greeter", "helloworld.Greeter", true),
+            ("helloworld.*", "helloworld.Greeter", true),
+            ("*.Greeter", "helloworld.v1.Greeter", true),
+            ("*", "", true),
+            ("Say*", "SayHello", true),
+            ("*Hello*", "SayHelloAgain", true),
+            ("a*b*c", "aXbYbZc", true),
+            ("a*b*c", "aXbYc-", false),
+            ("helloworld.*", "helloworld", false),
+            ("Greeter", "greeter", false),
+        ];
+
+        for (pattern, s, expected) in cases {
+            assert_eq!(wildcard_match(pattern, s), expected, "{} {}", pattern, s);
+        }
+    }
+
+    #[test]
+    fn grpc_usage() {
+        let rules = GrpcRuleSet::from(vec![
+            GrpcRule::new("*", "*", "grpc_calls", 1, false),
+            GrpcRule::new("helloworld.Greeter", "Say*", "greetings", 2, false),
+            GrpcRule::new("grpc.health.v1.Health", "*", "health", 1, true),
+            GrpcRule::new("*", "Check", "checks", 1, false),
+        ]);
+
+        assert_eq!(
+            rules.usage("helloworld.Greeter", "SayHello"),
+            vec![("grpc_calls", 1), ("greetings", 2)]
+        );
+        assert_eq!(
+            rules.usage_for_request(&Method::POST, "/grpc.health.v1.Health/Check"),
+            vec![("grpc_calls", 1), ("health", 1)]
+        );
+        assert_eq!(
+            rules.usage_for_request(&Method::POST, "/other.Service/Check"),
+            vec![("grpc_calls", 1), ("checks", 1)]
+        );
+        assert!(rules
+            .usage_for_request(&Method::GET, "/helloworld.Greeter/SayHello")
+            .is_empty());
+        assert!(rules
+            .usage_for_request(&Method::POST, "/helloworld.Greeter")
+            .is_empty());
+        assert!(rules.as_slice()[1].matches_path("/helloworld.Greeter/SayHi"));
+        assert!(rules.as_slice()[1].matches_request(&Method::POST, "/helloworld.Greeter/SayHi"));
+    }
+
+    #[test]
+    fn build_from_descriptor_set() {
+        let method = |name: &str| MethodDescriptorProto {
+            name: Some(name.to_string()),
+            ..Default::default()
+        };
+        let set = FileDescriptorSet {
+            file: vec![
+                FileDescriptorProto {
+                    package: Some("helloworld".to_string()),
+                    service: vec![ServiceDescriptorProto {
+                        name: Some("Greeter".to_string()),
+                        method: vec![method("SayHello"), method("SayHelloAgain")],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                FileDescriptorProto {
+                    service: vec![ServiceDescriptorProto {
+                        name: Some("Unpackaged".to_string()),
+                        method: vec![method("Call")],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            ],
+        };
+        let bytes = set.encode_to_vec();
+
+        let rules = GrpcRuleSet::from_descriptor_set(&bytes).unwrap();
+        assert_eq!(
+            rules
+                .iter()
+                .map(|r| (r.service(), r.method(), r.metric()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "helloworld.Greeter",
+                    "SayHello",
+                    "helloworld_Greeter_SayHello"
+                ),
+                (
+                    "helloworld.Greeter",
+                    "SayHelloAgain",
+                    "helloworld_Greeter_SayHelloAgain"
+                ),
+                ("Unpackaged", "Call", "Unpackaged_Call"),
+            ]
+        );
+        assert_eq!(
+            rules.usage("helloworld.Greeter", "SayHello"),
+            vec![("helloworld_Greeter_SayHello", 1)]
+        );
+
+        let rules = GrpcRuleSet::from_descriptor_set_with(&bytes, |_, m| m.to_lowercase()).unwrap();
+        assert_eq!(rules.as_slice()[2].metric(), "call");
+
+        assert!(GrpcRuleSet::from_descriptor_set(b"\xff\xff").is_err());
+    }
+}
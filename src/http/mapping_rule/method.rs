@@ -3,6 +3,8 @@ use std::prelude::v1::*;
 #[cfg(feature = "rest-mappings-serde")]
 use serde::{Deserialize, Serialize};
 
+use core::convert::TryFrom;
+
 use crate::util::string::AllCaps;
 
 #[cfg_attr(
@@ -106,6 +108,65 @@ impl Method {
     }
 }
 
+impl From<crate::http::Method> for Method {
+    fn from(m: crate::http::Method) -> Self {
+        use crate::http::Method as HttpMethod;
+
+        match m {
+            HttpMethod::GET => Self::GET,
+            HttpMethod::POST => Self::POST,
+            HttpMethod::PUT => Self::PUT,
+            HttpMethod::PATCH => Self::PATCH,
+            HttpMethod::HEAD => Self::HEAD,
+            HttpMethod::DELETE => Self::DELETE,
+        }
+    }
+}
+
+impl TryFrom<&Method> for crate::http::Method {
+    type Error = crate::Error;
+
+    fn try_from(m: &Method) -> Result<Self, Self::Error> {
+        Ok(match m {
+            Method::GET => Self::GET,
+            Method::POST => Self::POST,
+            Method::PUT => Self::PUT,
+            Method::PATCH => Self::PATCH,
+            Method::HEAD => Self::HEAD,
+            Method::DELETE => Self::DELETE,
+            _ => return Err(crate::Error::InvalidMethod(m.as_str().to_owned())),
+        })
+    }
+}
+
+#[cfg(feature = "http-types")]
+impl From<&http_types::Method> for Method {
+    fn from(m: &http_types::Method) -> Self {
+        Self::from(m.as_str())
+    }
+}
+
+#[cfg(feature = "http-types")]
+impl From<http_types::Method> for Method {
+    fn from(m: http_types::Method) -> Self {
+        Self::from(&m)
+    }
+}
+
+// Any stands for a set of methods, so it has no equivalent.
+#[cfg(feature = "http-types")]
+impl TryFrom<&Method> for http_types::Method {
+    type Error = crate::Error;
+
+    fn try_from(m: &Method) -> Result<Self, Self::Error> {
+        match m {
+            Method::Any => Err(crate::Error::InvalidMethod(m.as_str().to_owned())),
+            _ => Self::from_bytes(m.as_str().as_bytes())
+                .map_err(|_| crate::Error::InvalidMethod(m.as_str().to_owned())),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -202,4 +263,40 @@ mod test {
         assert_eq!(method, &other_string_method);
         assert_eq!(method, &other_str_method);
     }
+
+    #[test]
+    fn conversions_with_http_methods() {
+        use crate::http::Method as HttpMethod;
+
+        for method in [HttpMethod::GET, HttpMethod::DELETE, HttpMethod::PATCH] {
+            let rule_method = Method::from(method);
+
+            assert_eq!(rule_method.as_str(), method.as_str());
+            assert_eq!(HttpMethod::try_from(&rule_method).unwrap(), method);
+        }
+
+        assert!(HttpMethod::try_from(&Method::Any).is_err());
+        assert!(HttpMethod::try_from(&Method::OPTIONS).is_err());
+    }
+
+    #[cfg(feature = "http-types")]
+    #[test]
+    fn conversions_with_http_types() {
+        use http_types::Method as HTTPMethod;
+
+        assert!(matches!(Method::from(HTTPMethod::OPTIONS), Method::OPTIONS));
+        assert!(matches!(
+            Method::from(&HTTPMethod::from_bytes(b"PURGE").unwrap()),
+            Method::Other(ref m) if m.as_str() == "PURGE"
+        ));
+        assert_eq!(
+            HTTPMethod::try_from(&Method::from("purge")).unwrap(),
+            HTTPMethod::from_bytes(b"PURGE").unwrap()
+        );
+        assert_eq!(
+            HTTPMethod::try_from(&Method::TRACE).unwrap(),
+            HTTPMethod::TRACE
+        );
+        assert!(HTTPMethod::try_from(&Method::Any).is_err());
+    }
 }
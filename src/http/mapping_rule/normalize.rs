@@ -0,0 +1,295 @@
+// Normalization of request paths and query strings before matching them against rules.
+//
+// Equivalent URIs can be written in different ways, and the backends behind a gateway will
+// usually treat them as the same resource. If rules only ever saw the raw form, requests could
+// dodge a rule (ie. `/%61dmin` vs `/admin`) or trigger one they should not. Patterns are
+// normalized with the same settings when compiled, so that they keep matching their intended
+// requests.
+use std::prelude::v1::*;
+
+use super::escaping;
+
+/// How to treat `+` characters in query strings.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Plus {
+    /// A `+` is a literal plus sign, as per RFC 3986.
+    Literal,
+    /// A `+` is an encoded space, as in HTML forms, and is equivalent to `%20`.
+    Space,
+}
+
+/// Settings for the normalization of paths and query strings.
+///
+/// Duplicated forward slashes in paths are always coalesced. By default, percent-encoded
+/// unreserved characters are decoded, hexadecimal digits in the remaining percent-encodings
+/// are uppercased, and `.` and `..` path segments are resolved as specified by RFC 3986.
+///
+/// # Examples
+///
+/// ```
+/// use threescalers::http::mapping_rule::{Normalization, Plus};
+///
+/// let normalization = Normalization::new();
+/// assert_eq!(normalization.path("/a/./b//../%7euser%2f"), "/a/~user%2F");
+///
+/// let normalization = normalization.decode_slashes(true).plus(Plus::Space);
+/// assert_eq!(normalization.path("/a/%7euser%2f"), "/a/~user/");
+/// assert_eq!(normalization.query_string("q=a+b"), "q=a%20b");
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Normalization {
+    dot_segments: bool,
+    unreserved: bool,
+    slashes: bool,
+    plus: Plus,
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Normalization {
+    pub fn new() -> Self {
+        Self {
+            dot_segments: true,
+            unreserved: true,
+            slashes: false,
+            plus: Plus::Literal,
+        }
+    }
+
+    /// Only coalesces duplicated forward slashes in paths.
+    pub fn none() -> Self {
+        Self {
+            dot_segments: false,
+            unreserved: false,
+            slashes: false,
+            plus: Plus::Literal,
+        }
+    }
+
+    /// Whether to resolve `.` and `..` path segments.
+    pub fn dot_segments(mut self, enabled: bool) -> Self {
+        self.dot_segments = enabled;
+        self
+    }
+
+    /// Whether to decode percent-encoded unreserved characters and uppercase the hexadecimal
+    /// digits of the rest of percent-encodings.
+    pub fn unreserved(mut self, enabled: bool) -> Self {
+        self.unreserved = enabled;
+        self
+    }
+
+    /// Whether to decode `%2F` into a forward slash in paths. This is off by default since it
+    /// changes the path segments, but should be enabled if the upstream API decodes them.
+    pub fn decode_slashes(mut self, enabled: bool) -> Self {
+        self.slashes = enabled;
+        self
+    }
+
+    pub fn plus(mut self, plus: Plus) -> Self {
+        self.plus = plus;
+        self
+    }
+
+    /// Normalizes the path of a request.
+    pub fn path(&self, path: &str) -> String {
+        let path = self.percent_encodings(path, true, false);
+        let path = escaping::coalesce_chars(path.as_str(), '/');
+
+        if self.dot_segments {
+            remove_dot_segments(path.as_str(), false)
+        } else {
+            path
+        }
+    }
+
+    /// Normalizes the query string of a request.
+    pub fn query_string(&self, qs: &str) -> String {
+        let qs = self.percent_encodings(qs, false, false);
+
+        match self.plus {
+            Plus::Literal => qs,
+            Plus::Space => qs.replace('+', "%20"),
+        }
+    }
+
+    // Path patterns are regular expressions with placeholders, so these are left alone and
+    // decoded characters are escaped when needed. A trailing `$` is an anchor, not part of the
+    // last segment.
+    pub(super) fn path_pattern(&self, pattern: &str) -> String {
+        let (pattern, anchor) = match pattern.strip_suffix('$') {
+            Some(p) if !p.ends_with('\\') => (p, "$"),
+            _ => (pattern, ""),
+        };
+        let pattern = escaping::map_literals(pattern, |literal| {
+            self.percent_encodings(literal, true, true)
+        });
+        let pattern = escaping::coalesce_chars(pattern.as_str(), '/');
+        let mut pattern = if self.dot_segments {
+            remove_dot_segments(pattern.as_str(), true)
+        } else {
+            pattern
+        };
+
+        pattern.push_str(anchor);
+        pattern
+    }
+
+    // Query string patterns are escaped when compiled, so only placeholders are left alone.
+    pub(super) fn query_string_pattern(&self, pattern: &str) -> String {
+        escaping::map_literals(pattern, |literal| self.query_string(literal))
+    }
+
+    fn percent_encodings(&self, s: &str, in_path: bool, escape_dots: bool) -> String {
+        if !(self.unreserved || (in_path && self.slashes)) {
+            return s.to_string();
+        }
+
+        let bytes = s.as_bytes();
+        let mut normalized = String::with_capacity(s.len());
+        let mut idx = 0;
+
+        while let Some(c) = s[idx..].chars().next() {
+            let decoded = match (c, bytes.get(idx + 1..idx + 3)) {
+                ('%', Some(&[hi, lo])) => {
+                    hex_value(hi).zip(hex_value(lo)).map(|(h, l)| (h << 4) | l)
+                }
+                _ => None,
+            };
+
+            match decoded.map(char::from) {
+                Some('/') if in_path && self.slashes => normalized.push('/'),
+                Some('.') if self.unreserved && escape_dots => normalized.push_str(r"\."),
+                Some(d) if self.unreserved && is_unreserved(d) => normalized.push(d),
+                Some(_) if self.unreserved => {
+                    normalized.push_str(&s[idx..idx + 3].to_ascii_uppercase());
+                }
+                Some(_) => normalized.push_str(&s[idx..idx + 3]),
+                None => {
+                    normalized.push(c);
+                    idx += c.len_utf8();
+                    continue;
+                }
+            }
+            idx += 3;
+        }
+
+        normalized
+    }
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    char::from(b).to_digit(16).map(|d| d as u8)
+}
+
+fn is_unreserved(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~')
+}
+
+// RFC 3986 section 5.2.4, working on whole segments. Patterns can also have escaped dots.
+fn remove_dot_segments(path: &str, pattern: bool) -> String {
+    let absolute = path.starts_with('/');
+    let mut segments = path.split('/').collect::<Vec<_>>();
+    if absolute {
+        segments.remove(0);
+    }
+
+    let last = segments.len().saturating_sub(1);
+    let mut output: Vec<&str> = Vec::with_capacity(segments.len());
+    let mut trailing_slash = false;
+
+    for (idx, segment) in segments.into_iter().enumerate() {
+        let unescaped = if pattern && segment.len() <= 4 {
+            segment.replace(r"\.", ".")
+        } else {
+            String::new()
+        };
+
+        trailing_slash = false;
+        match (segment, unescaped.as_str()) {
+            (".", _) | (_, ".") => trailing_slash = idx == last,
+            ("..", _) | (_, "..") => {
+                output.pop();
+                trailing_slash = idx == last;
+            }
+            (s, _) => output.push(s),
+        }
+    }
+
+    let mut normalized = String::with_capacity(path.len());
+    if absolute {
+        normalized.push('/');
+    }
+    normalized.push_str(output.join("/").as_str());
+    if trailing_slash && !normalized.is_empty() && !normalized.ends_with('/') {
+        normalized.push('/');
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_paths() {
+        let normalization = Normalization::new();
+        let cases = [
+            ("/a/b/c/./../../g", "/a/g"),
+            ("/a/b/..", "/a/"),
+            ("/a/b/.", "/a/b/"),
+            ("/../a", "/a"),
+            ("/..", "/"),
+            ("//a//./b", "/a/b"),
+            ("/%7Euser/%2e%2E/%61dmin", "/admin"),
+            ("/a%2fb/%c3%A9", "/a%2Fb/%C3%A9"),
+            ("/50%/x%zz/%e", "/50%/x%zz/%e"),
+            ("/a/..b/.c/", "/a/..b/.c/"),
+            ("/é/./", "/é/"),
+        ];
+
+        for (path, expected) in cases {
+            assert_eq!(normalization.path(path), expected, "{}", path);
+        }
+
+        assert_eq!(Normalization::none().path("//a/./%7e/%2f"), "/a/./%7e/%2f");
+        assert_eq!(
+            Normalization::none().decode_slashes(true).path("/a%2fb"),
+            "/a/b"
+        );
+    }
+
+    #[test]
+    fn normalize_query_strings() {
+        let normalization = Normalization::new();
+
+        assert_eq!(normalization.query_string("a=%7e+%26&b"), "a=~+%26&b");
+        assert_eq!(
+            normalization.plus(Plus::Space).query_string("q=a+b%2b"),
+            "q=a%20b%2B"
+        );
+    }
+
+    #[test]
+    fn normalize_patterns() {
+        let normalization = Normalization::new();
+
+        assert_eq!(
+            normalization.path_pattern("/a/{b}/../%7E{c%41}/%2e%2E/%2Ejson$"),
+            r"/a/\.json$"
+        );
+        assert_eq!(normalization.path_pattern(r"/a/.\$"), r"/a/.\$");
+        assert_eq!(normalization.path_pattern("/v1/{id}/./x"), "/v1/{id}/x");
+        assert_eq!(
+            normalization
+                .plus(Plus::Space)
+                .query_string_pattern("q={a+b}+%7e"),
+            "q={a+b}%20~"
+        );
+    }
+}
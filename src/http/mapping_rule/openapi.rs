@@ -0,0 +1,477 @@
+// Import of mapping rules from OpenAPI 3 documents.
+//
+// Each operation becomes a rule matching its method and exact path, with OpenAPI's `{param}`
+// path templates becoming placeholders, and incrementing a metric named after its operationId.
+use std::prelude::v1::*;
+
+use core::fmt;
+
+use serde::{de, Deserialize, Deserializer};
+
+use super::{MappingRule, Method, RestRule, RuleSet};
+use crate::{Error, ParseError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SkipReason {
+    /// The operation has no operationId to name the metric after.
+    MissingOperationId,
+    /// The path contains a character with a special meaning in mapping rule patterns.
+    UnsupportedCharacter(char),
+    /// The path item is a reference to another document.
+    Reference,
+    /// The resulting pattern could not be compiled.
+    InvalidPattern,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingOperationId => f.write_str("missing operationId"),
+            Self::UnsupportedCharacter(c) => write!(f, "unsupported character {:?} in path", c),
+            Self::Reference => f.write_str("path item references are not supported"),
+            Self::InvalidPattern => f.write_str("the path results in an invalid pattern"),
+        }
+    }
+}
+
+/// An operation that could not be represented as a mapping rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Skipped {
+    method: Option<Method>,
+    path: String,
+    reason: SkipReason,
+}
+
+impl Skipped {
+    /// The method of the operation, not present for path items that could not be inspected.
+    pub fn method(&self) -> Option<&Method> {
+        self.method.as_ref()
+    }
+
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    pub fn reason(&self) -> &SkipReason {
+        &self.reason
+    }
+}
+
+/// The result of importing an OpenAPI document.
+#[derive(Debug, Clone)]
+pub struct Import {
+    rules: RuleSet,
+    skipped: Vec<Skipped>,
+}
+
+impl Import {
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    /// The operations that could not be represented as mapping rules.
+    pub fn skipped(&self) -> &[Skipped] {
+        self.skipped.as_slice()
+    }
+
+    pub fn into_parts(self) -> (RuleSet, Vec<Skipped>) {
+        (self.rules, self.skipped)
+    }
+}
+
+/// Converts OpenAPI 3 documents into mapping rules.
+///
+/// # Examples
+///
+/// ```
+/// use threescalers::http::mapping_rule::{openapi::Importer, Method};
+///
+/// let doc = r#"{
+///     "openapi": "3.0.3",
+///     "paths": {
+///         "/pets/{petId}": {"get": {"operationId": "showPetById"}}
+///     }
+/// }"#;
+/// let import = Importer::new().import_json(doc).unwrap();
+///
+/// assert_eq!(import.rules().usage(&Method::GET, "/pets/1"), vec![("showPetById", 1)]);
+/// assert!(import.skipped().is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Importer {
+    delta: u64,
+    server_path: bool,
+}
+
+impl Default for Importer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Importer {
+    pub fn new() -> Self {
+        Self {
+            delta: 1,
+            server_path: true,
+        }
+    }
+
+    /// Sets the delta of the generated rules, 1 by default.
+    pub fn delta(mut self, delta: u64) -> Self {
+        self.delta = delta;
+        self
+    }
+
+    /// Whether to prefix patterns with the path of the first server URL, as OpenAPI paths are
+    /// relative to it. Enabled by default.
+    pub fn server_path(mut self, enabled: bool) -> Self {
+        self.server_path = enabled;
+        self
+    }
+
+    pub fn import_json(&self, document: &str) -> Result<Import, Error> {
+        let document = serde_json::from_str::<Document>(document)
+            .map_err(|e| ParseError::new("failed to parse OpenAPI JSON document").with_source(e))?;
+
+        self.import(document)
+    }
+
+    pub fn import_yaml(&self, document: &str) -> Result<Import, Error> {
+        let document = serde_yaml::from_str::<Document>(document)
+            .map_err(|e| ParseError::new("failed to parse OpenAPI YAML document").with_source(e))?;
+
+        self.import(document)
+    }
+
+    fn import(&self, document: Document) -> Result<Import, Error> {
+        if !document.openapi.starts_with("3.") {
+            return Err(Error::Parse(ParseError::new(format!(
+                "unsupported OpenAPI version {:?}, expected 3.x",
+                document.openapi
+            ))));
+        }
+
+        let base_path = if self.server_path {
+            document
+                .servers
+                .first()
+                .map(|server| server_path(server.url.as_str()))
+                .transpose()?
+                .unwrap_or_default()
+        } else {
+            String::new()
+        };
+
+        let mut rules = RuleSet::new();
+        let mut skipped = Vec::new();
+
+        for (path, item) in document.paths.0 {
+            let full_path = [base_path.as_str(), path.as_str()].concat();
+
+            if item.reference.is_some() {
+                skipped.push(Skipped {
+                    method: None,
+                    path: full_path,
+                    reason: SkipReason::Reference,
+                });
+                continue;
+            }
+
+            for (method, operation) in item.operations() {
+                match self.rule(method.clone(), full_path.as_str(), operation) {
+                    Ok(rule) => rules.push(rule),
+                    Err(reason) => skipped.push(Skipped {
+                        method: Some(method),
+                        path: full_path.clone(),
+                        reason,
+                    }),
+                }
+            }
+        }
+
+        Ok(Import { rules, skipped })
+    }
+
+    fn rule(&self, method: Method, path: &str, op: &Operation) -> Result<MappingRule, SkipReason> {
+        let operation_id = op
+            .operation_id
+            .as_deref()
+            .ok_or(SkipReason::MissingOperationId)?;
+        let pattern = path_pattern(path)?;
+        let rule = RestRule::new(method, pattern).map_err(|_| SkipReason::InvalidPattern)?;
+
+        Ok(MappingRule::new(
+            rule,
+            metric_name(operation_id),
+            self.delta,
+            false,
+        ))
+    }
+}
+
+// OpenAPI paths are matched exactly, and templates already look like 3scale placeholders. The
+// only literal characters that need escaping are dollar signs, while other regex operators are
+// rejected as they cannot be expressed in patterns.
+fn path_pattern(path: &str) -> Result<String, SkipReason> {
+    let mut pattern = String::with_capacity(path.len() + 1);
+    let mut in_template = false;
+
+    for c in path.chars() {
+        match c {
+            '{' => in_template = true,
+            '}' => in_template = false,
+            '$' if !in_template => pattern.push('\\'),
+            '\\' | '^' | '*' | '+' | '?' | '(' | ')' | '[' | ']' | '|' if !in_template => {
+                return Err(SkipReason::UnsupportedCharacter(c))
+            }
+            _ => (),
+        }
+        pattern.push(c);
+    }
+
+    pattern.push('$');
+    Ok(pattern)
+}
+
+// Metric system names only take alphanumeric characters, hyphens and underscores.
+fn metric_name(operation_id: &str) -> String {
+    operation_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// Variables in the scheme or host do not matter for the path, only those in the path do.
+fn server_path(url: &str) -> Result<String, Error> {
+    let path = match url.find("://") {
+        Some(idx) => {
+            let rest = &url[idx + 3..];
+            rest.find('/').map_or("", |idx| &rest[idx..])
+        }
+        None => url,
+    };
+
+    if path.contains('{') {
+        return Err(Error::Parse(ParseError::new(format!(
+            "server URL path variables are not supported: {}",
+            url
+        ))));
+    }
+
+    Ok(path.trim_end_matches('/').to_string())
+}
+
+#[derive(Deserialize)]
+struct Document {
+    openapi: String,
+    #[serde(default)]
+    servers: Vec<Server>,
+    #[serde(default)]
+    paths: Paths,
+}
+
+#[derive(Deserialize)]
+struct Server {
+    url: String,
+}
+
+// Paths keep the order in which they appear in the document.
+#[derive(Default)]
+struct Paths(Vec<(String, PathItem)>);
+
+impl<'de> Deserialize<'de> for Paths {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PathsVisitor;
+
+        impl<'de> de::Visitor<'de> for PathsVisitor {
+            type Value = Paths;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map of paths to path items")
+            }
+
+            fn visit_map<V: de::MapAccess<'de>>(self, mut map: V) -> Result<Paths, V::Error> {
+                let mut paths = Vec::with_capacity(map.size_hint().unwrap_or(16));
+
+                while let Some(path) = map.next_key::<String>()? {
+                    // specification extensions can be interleaved with paths
+                    if path.starts_with("x-") {
+                        map.next_value::<de::IgnoredAny>()?;
+                    } else {
+                        paths.push((path, map.next_value::<PathItem>()?));
+                    }
+                }
+
+                Ok(Paths(paths))
+            }
+        }
+
+        deserializer.deserialize_map(PathsVisitor)
+    }
+}
+
+#[derive(Deserialize)]
+struct PathItem {
+    #[serde(rename = "$ref")]
+    reference: Option<String>,
+    get: Option<Operation>,
+    put: Option<Operation>,
+    post: Option<Operation>,
+    delete: Option<Operation>,
+    options: Option<Operation>,
+    head: Option<Operation>,
+    patch: Option<Operation>,
+    trace: Option<Operation>,
+}
+
+impl PathItem {
+    fn operations(&self) -> impl Iterator<Item = (Method, &Operation)> {
+        [
+            (Method::GET, self.get.as_ref()),
+            (Method::PUT, self.put.as_ref()),
+            (Method::POST, self.post.as_ref()),
+            (Method::DELETE, self.delete.as_ref()),
+            (Method::OPTIONS, self.options.as_ref()),
+            (Method::HEAD, self.head.as_ref()),
+            (Method::PATCH, self.patch.as_ref()),
+            (Method::TRACE, self.trace.as_ref()),
+        ]
+        .into_iter()
+        .filter_map(|(method, op)| op.map(|op| (method, op)))
+    }
+}
+
+#[derive(Deserialize)]
+struct Operation {
+    #[serde(rename = "operationId")]
+    operation_id: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PETSTORE_YAML: &str = r#"
+openapi: "3.0.0"
+info:
+  version: 1.0.0
+  title: Swagger Petstore
+servers:
+  - url: http://petstore.swagger.io/v1/
+paths:
+  /pets:
+    get:
+      operationId: listPets
+      responses:
+        '200':
+          description: A paged array of pets
+    post:
+      operationId: pets.create
+  /pets/{petId}:
+    parameters:
+      - name: petId
+        in: path
+        required: true
+    get:
+      operationId: showPetById
+    delete:
+      summary: Delete a pet
+  /pets/{petId}/price$:
+    get:
+      operationId: showPrice
+  /search(.*):
+    get:
+      operationId: search
+  /shared:
+    $ref: "./shared.yaml#/paths/shared"
+  x-internal: true
+"#;
+
+    #[test]
+    fn import_yaml_document() {
+        let import = Importer::new().import_yaml(PETSTORE_YAML).unwrap();
+        let rules = import.rules();
+
+        assert_eq!(
+            rules.iter().map(|r| r.rule().pattern()).collect::<Vec<_>>(),
+            vec![
+                "/v1/pets$",
+                "/v1/pets$",
+                "/v1/pets/{petId}$",
+                r"/v1/pets/{petId}/price\$$"
+            ]
+        );
+        assert_eq!(
+            rules.iter().map(MappingRule::metric).collect::<Vec<_>>(),
+            vec!["listPets", "pets_create", "showPetById", "showPrice"]
+        );
+        assert_eq!(rules.usage(&Method::GET, "/v1/pets"), vec![("listPets", 1)]);
+        assert_eq!(rules.usage(&Method::GET, "/v1/pets/"), vec![]);
+        assert_eq!(
+            rules.usage(&Method::POST, "/v1/pets"),
+            vec![("pets_create", 1)]
+        );
+        assert_eq!(
+            rules.usage(&Method::GET, "/v1/pets/7"),
+            vec![("showPetById", 1)]
+        );
+        assert_eq!(
+            rules.usage(&Method::GET, "/v1/pets/7/price$"),
+            vec![("showPrice", 1)]
+        );
+
+        let skipped = import.skipped();
+        assert_eq!(skipped.len(), 3);
+        assert_eq!(skipped[0].method(), Some(&Method::DELETE));
+        assert_eq!(skipped[0].path(), "/v1/pets/{petId}");
+        assert_eq!(skipped[0].reason(), &SkipReason::MissingOperationId);
+        assert_eq!(skipped[1].path(), "/v1/search(.*)");
+        assert_eq!(skipped[1].reason(), &SkipReason::UnsupportedCharacter('('));
+        assert_eq!(skipped[2].method(), None);
+        assert_eq!(skipped[2].reason(), &SkipReason::Reference);
+    }
+
+    #[test]
+    fn import_json_document() {
+        let json = r#"{
+            "openapi": "3.1.0",
+            "servers": [{"url": "https://{region}.example.com/api"}],
+            "paths": {
+                "/orders/{id}": {"patch": {"operationId": "updateOrder"}}
+            }
+        }"#;
+
+        let import = Importer::new().delta(3).import_json(json).unwrap();
+        assert_eq!(
+            import.rules().usage(&Method::PATCH, "/api/orders/1"),
+            vec![("updateOrder", 3)]
+        );
+
+        let json = json.replace("/api", "/{version}");
+        assert!(Importer::new().import_json(&json).is_err());
+
+        let import = Importer::new()
+            .server_path(false)
+            .import_json(&json)
+            .unwrap();
+        assert_eq!(
+            import.rules().usage(&Method::PATCH, "/orders/1"),
+            vec![("updateOrder", 1)]
+        );
+    }
+
+    #[test]
+    fn reject_other_versions() {
+        let json = r#"{"swagger": "2.0", "openapi": "2.0", "paths": {}}"#;
+
+        assert!(Importer::new().import_json(json).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+use std::prelude::v1::*;
+
+use std::{iter::FromIterator, slice::Iter, vec::IntoIter};
+
+use super::{Method, RestRule};
+
+/// A `RestRule` bound to the metric it increments, as configured in 3scale's `proxy_rules`.
+#[derive(Debug, Clone)]
+pub struct MappingRule {
+    rule: RestRule,
+    metric: String,
+    delta: u64,
+    last: bool,
+}
+
+impl MappingRule {
+    pub fn new<S: Into<String>>(rule: RestRule, metric: S, delta: u64, last: bool) -> Self {
+        Self {
+            rule,
+            metric: metric.into(),
+            delta,
+            last,
+        }
+    }
+
+    pub fn rule(&self) -> &RestRule {
+        &self.rule
+    }
+
+    pub fn metric(&self) -> &str {
+        self.metric.as_str()
+    }
+
+    pub fn delta(&self) -> u64 {
+        self.delta
+    }
+
+    /// Whether matching this rule stops the evaluation of the rules that follow it.
+    pub fn is_last(&self) -> bool {
+        self.last
+    }
+
+    pub fn matches<S: AsRef<str>>(&self, method: &Method, path_qs: S) -> bool {
+        self.rule.matches(method, path_qs)
+    }
+}
+
+/// An ordered set of mapping rules.
+///
+/// Rules are evaluated in order and every matching rule adds its delta to its metric, until a
+/// matching rule marked as `last` is found.
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet(Vec<MappingRule>);
+
+impl RuleSet {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self(Vec::with_capacity(capacity))
+    }
+
+    pub fn push(&mut self, rule: MappingRule) {
+        self.0.push(rule);
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> Iter<'_, MappingRule> {
+        self.0.iter()
+    }
+
+    pub fn as_slice(&self) -> &[MappingRule] {
+        self.0.as_slice()
+    }
+
+    pub fn into_inner(self) -> Vec<MappingRule> {
+        self.0
+    }
+
+    /// Returns the rules matching a request in evaluation order, stopping after the first
+    /// matching rule marked as `last`.
+    pub fn matching<S: AsRef<str>>(&self, method: &Method, path_qs: S) -> Vec<&MappingRule> {
+        let path_qs = path_qs.as_ref();
+        let mut matched = Vec::new();
+
+        for rule in self.0.iter().filter(|r| r.matches(method, path_qs)) {
+            matched.push(rule);
+            if rule.is_last() {
+                break;
+            }
+        }
+
+        matched
+    }
+
+    /// Computes the usage a request should report, adding up the deltas of all matching rules
+    /// per metric. Metrics are listed in the order they were first matched.
+    pub fn usage<S: AsRef<str>>(&self, method: &Method, path_qs: S) -> Vec<(&str, u64)> {
+        add_up_usage(
+            self.matching(method, path_qs)
+                .into_iter()
+                .map(|rule| (rule.metric(), rule.delta())),
+        )
+    }
+}
+
+// Adds up deltas per metric, keeping metrics in the order they were first seen.
+pub(super) fn add_up_usage<'a, I: IntoIterator<Item = (&'a str, u64)>>(
+    deltas: I,
+) -> Vec<(&'a str, u64)> {
+    deltas
+        .into_iter()
+        .fold(Vec::new(), |mut acc: Vec<(&str, u64)>, (metric, delta)| {
+            match acc.iter_mut().find(|(m, _)| *m == metric) {
+                Some((_, total)) => *total = total.saturating_add(delta),
+                None => acc.push((metric, delta)),
+            }
+            acc
+        })
+}
+
+impl From<Vec<MappingRule>> for RuleSet {
+    fn from(rules: Vec<MappingRule>) -> Self {
+        Self(rules)
+    }
+}
+
+impl FromIterator<MappingRule> for RuleSet {
+    fn from_iter<T: IntoIterator<Item = MappingRule>>(iter: T) -> Self {
+        Self(Vec::from_iter(iter))
+    }
+}
+
+impl Extend<MappingRule> for RuleSet {
+    fn extend<T: IntoIterator<Item = MappingRule>>(&mut self, iter: T) {
+        self.0.extend(iter);
+    }
+}
+
+impl<'r> IntoIterator for &'r RuleSet {
+    type IntoIter = Iter<'r, MappingRule>;
+    type Item = &'r MappingRule;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl IntoIterator for RuleSet {
+    type IntoIter = IntoIter<MappingRule>;
+    type Item = MappingRule;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(method: &str, pattern: &str, metric: &str, delta: u64, last: bool) -> MappingRule {
+        MappingRule::new(RestRule::new(method, pattern).unwrap(), metric, delta, last)
+    }
+
+    #[test]
+    fn usage_adds_up_deltas_per_metric() {
+        let rules = [
+            rule("any", "/", "hits", 1, false),
+            rule("get", "/products", "products", 2, false),
+            rule("get", "/products/{id}", "products", 3, false),
+            rule("post", "/products", "creations", 1, false),
+        ]
+        .into_iter()
+        .collect::<RuleSet>();
+
+        assert_eq!(
+            rules.usage(&Method::GET, "/products/1"),
+            vec![("hits", 1), ("products", 5)]
+        );
+        assert_eq!(
+            rules.usage(&Method::POST, "/products"),
+            vec![("hits", 1), ("creations", 1)]
+        );
+        assert_eq!(rules.usage(&Method::DELETE, "/products"), vec![("hits", 1)]);
+    }
+
+    #[test]
+    fn last_rule_stops_evaluation() {
+        let rules = RuleSet::from(vec![
+            rule("get", "/products", "products", 1, true),
+            rule("any", "/", "hits", 1, false),
+        ]);
+
+        let matched = rules.matching(&Method::GET, "/products?page=2");
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].metric(), "products");
+
+        assert_eq!(rules.usage(&Method::GET, "/other"), vec![("hits", 1)]);
+    }
+}
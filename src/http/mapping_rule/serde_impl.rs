@@ -2,7 +2,7 @@ use std::prelude::v1::*;
 
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-use super::{escaping, RestRule};
+use super::{escaping, MappingRule, RestRule, RuleSet};
 
 fn convert_escaping_error<E: de::Error>(ee: escaping::Error) -> E {
     match ee {
@@ -83,11 +83,71 @@ impl Serialize for RestRule {
 
         let mut state = serializer.serialize_struct("MappingRule", 2)?;
         state.serialize_field("method", self.method().as_str())?;
-        state.serialize_field("pattern", self.pattern().as_str())?;
+        state.serialize_field("pattern", self.source())?;
         state.end()
     }
 }
 
+// A rule as found in the `proxy_rules` of a 3scale proxy configuration.
+#[derive(Deserialize)]
+struct ProxyRule {
+    http_method: String,
+    pattern: String,
+    metric_system_name: String,
+    #[serde(default = "ProxyRule::default_delta")]
+    delta: u64,
+    #[serde(default)]
+    last: bool,
+}
+
+impl ProxyRule {
+    fn default_delta() -> u64 {
+        1
+    }
+}
+
+impl<'de> Deserialize<'de> for MappingRule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let proxy_rule = ProxyRule::deserialize(deserializer)?;
+        let rule = RestRule::new(proxy_rule.http_method, proxy_rule.pattern)
+            .map_err(convert_escaping_error)?;
+
+        Ok(MappingRule::new(
+            rule,
+            proxy_rule.metric_system_name,
+            proxy_rule.delta,
+            proxy_rule.last,
+        ))
+    }
+}
+
+impl Serialize for MappingRule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("ProxyRule", 5)?;
+        state.serialize_field("http_method", self.rule().method().as_str())?;
+        state.serialize_field("pattern", self.rule().source())?;
+        state.serialize_field("metric_system_name", self.metric())?;
+        state.serialize_field("delta", &self.delta())?;
+        state.serialize_field("last", &self.is_last())?;
+        state.end()
+    }
+}
+
+// Rule sets are (de)serialized as the list of rules in `proxy_rules`.
+impl<'de> Deserialize<'de> for RuleSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<MappingRule>::deserialize(deserializer).map(RuleSet::from)
+    }
+}
+
+impl Serialize for RuleSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -163,8 +223,79 @@ mod test {
                 .collect::<Vec<_>>()
         );
 
-        let expected_json = r#"{"method":"GET","pattern":"/some/{_}/id$?n={_}&order=asc"}"#;
+        let expected_json =
+            r#"{"method":"GET","pattern":"///some/{product}//id$?n={id}&order=asc"}"#;
         assert_eq!(json, expected_json);
+        assert_eq!(other.pattern(), "/some/{product}/id$?n={id}&order=asc");
+        assert_eq!(other.placeholders(), vec!["product", "id"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_proxy_rule() -> Result<(), serde_json::Error> {
+        let json = r#"{
+            "id": 375837,
+            "http_method": "POST",
+            "pattern": "/products/{id}$",
+            "metric_id": 2555418191876,
+            "metric_system_name": "products",
+            "delta": 2,
+            "last": true,
+            "position": 1
+        }"#;
+        let mapping_rule: MappingRule = serde_json::from_str(json)?;
+
+        assert_eq!(mapping_rule.rule().method().as_str(), "POST");
+        assert_eq!(mapping_rule.metric(), "products");
+        assert_eq!(mapping_rule.delta(), 2);
+        assert!(mapping_rule.is_last());
+        assert!(mapping_rule.matches(&"post".into(), "/products/1"));
+        assert!(!mapping_rule.matches(&"post".into(), "/products/1/stock"));
+
+        let json = r#"{"http_method": "GET", "pattern": "/", "metric_system_name": "hits"}"#;
+        let mapping_rule: MappingRule = serde_json::from_str(json)?;
+
+        assert_eq!(mapping_rule.delta(), 1);
+        assert!(!mapping_rule.is_last());
+
+        Ok(())
+    }
+
+    #[test]
+    fn serialize_rule_set() -> Result<(), serde_json::Error> {
+        let json = r#"[
+            {"http_method": "ANY", "pattern": "/", "metric_system_name": "hits"},
+            {
+                "http_method": "GET",
+                "pattern": "//products/{product_id}$?fmt={format}",
+                "metric_system_name": "products",
+                "delta": 2,
+                "last": true
+            }
+        ]"#;
+        let rules: RuleSet = serde_json::from_str(json)?;
+
+        assert_eq!(rules.len(), 2);
+        assert_eq!(
+            serde_json::to_value(&rules)?,
+            serde_json::json!([
+                {
+                    "http_method": "ANY",
+                    "pattern": "/",
+                    "metric_system_name": "hits",
+                    "delta": 1,
+                    "last": false
+                },
+                {
+                    "http_method": "GET",
+                    "pattern": "//products/{product_id}$?fmt={format}",
+                    "metric_system_name": "products",
+                    "delta": 2,
+                    "last": true
+                }
+            ])
+        );
 
         Ok(())
     }
@@ -4,7 +4,6 @@ use crate::{
     api_call::{Kind::*, *},
     application::*,
     user::*,
-    version::USER_AGENT,
     ToParams,
 };
 
@@ -12,12 +11,18 @@ use super::Parameters;
 
 #[cfg(any(feature = "curl-easy", feature = "curl-easy2"))]
 pub mod curl;
+mod headers;
 #[cfg(feature = "http-types")]
 mod http_types;
+#[cfg(feature = "hyper")]
+pub mod hyper;
 #[cfg(any(feature = "reqwest-sync", feature = "reqwest-async"))]
 mod reqwest;
+#[cfg(feature = "ureq")]
+pub mod ureq;
 
 pub use super::{HeaderMap, Method};
+pub use headers::{HeaderPolicy, ACCEPT_XML, FORM_URLENCODED};
 
 #[derive(Clone, Debug)]
 pub struct Request {
@@ -48,7 +53,7 @@ impl Request {
         }
     }
 
-    pub fn uri_and_body(&self) -> (Cow<str>, Option<&str>) {
+    pub fn uri_and_body(&self) -> (Cow<'_, str>, Option<&str>) {
         (
             self.parameters.path_and_query(self.path),
             self.parameters.body(),
@@ -65,8 +70,10 @@ pub trait SetupRequest<'client, P, Output> {
     fn setup_request(&'client mut self, r: Request, params: P) -> Output;
 }
 
-impl From<&ApiCall<'_>> for Request {
-    fn from(apicall: &ApiCall) -> Self {
+impl Request {
+    /// Builds the request for an API call with the headers of the given policy, ignoring the
+    /// policy set in the call, if any.
+    pub fn with_policy(apicall: &ApiCall, policy: &HeaderPolicy) -> Self {
         let (method, path) =
             Request::endpoint(apicall.kind(), apicall.application(), apicall.user());
 
@@ -75,16 +82,17 @@ impl From<&ApiCall<'_>> for Request {
 
         let parameters = Parameters::new(method, params.as_slice());
 
+        // extensions are URL encoded, so they are always valid header values
         let mut headers = apicall.extensions().map_or_else(
-            || HeaderMap::with_capacity(1),
+            || HeaderMap::with_capacity(4),
             |e| {
-                let mut hm = HeaderMap::with_capacity(2);
-                let _ = hm.insert("3scale-options".to_owned(), e.to_string());
+                let mut hm = HeaderMap::with_capacity(5);
+                hm.insert_unchecked("3scale-options", e.to_string());
                 hm
             },
         );
 
-        headers.insert("User-Agent".to_owned(), USER_AGENT.to_owned());
+        policy.apply(apicall.kind(), parameters.body().is_some(), &mut headers);
 
         Request {
             method,
@@ -94,3 +102,12 @@ impl From<&ApiCall<'_>> for Request {
         }
     }
 }
+
+impl From<&ApiCall<'_>> for Request {
+    fn from(apicall: &ApiCall) -> Self {
+        match apicall.header_policy() {
+            Some(policy) => Request::with_policy(apicall, policy),
+            None => Request::with_policy(apicall, &HeaderPolicy::default()),
+        }
+    }
+}
@@ -3,7 +3,7 @@ use std::prelude::v1::*;
 use curl::easy::List;
 
 use super::HeaderMap;
-use crate::{anyhow, Error};
+use crate::{ClientError, ClientErrorKind, Error};
 use core::convert::TryFrom;
 
 impl TryFrom<&HeaderMap> for List {
@@ -13,9 +13,9 @@ impl TryFrom<&HeaderMap> for List {
         let mut list = List::new();
 
         for (k, v) in hm.iter() {
-            let header = [k.as_str(), ": ", v.as_str()].concat();
+            let header = [k, ": ", v].concat();
             list.append(header.as_str())
-                .map_err(|e| anyhow!("failed to add a node to a curl List: {:#?}", e))?;
+                .map_err(|e| setopt_error(e, "failed to append header"))?;
         }
 
         Ok(list)
@@ -23,6 +23,10 @@ impl TryFrom<&HeaderMap> for List {
 }
 
 // Common functions for curl clients
+pub(crate) fn setopt_error(e: curl::Error, message: &str) -> ClientError {
+    ClientError::new(ClientErrorKind::Request, message).with_source(e)
+}
+
 pub fn copy_data(offset: &mut usize, source: &[u8], dst: &mut [u8]) -> usize {
     let bytes = &source[*offset..];
     let len = bytes.len();
@@ -40,3 +44,22 @@ mod easy2;
 pub use easy::CurlEasyClient;
 #[cfg(feature = "curl-easy2")]
 pub use easy2::{BodyHandle, SetBody};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_append_errors_keep_their_source() {
+        // Collected headers are not validated, so curl gets to reject the NUL byte.
+        let headers = [("X-Header", "a\0b")].into_iter().collect::<HeaderMap>();
+
+        match List::try_from(&headers) {
+            Err(Error::Client(e)) => {
+                assert_eq!(e.kind(), ClientErrorKind::Request);
+                assert!(std::error::Error::source(&e).is_some());
+            }
+            r => core::panic!("unexpected result {:?}", r.map(|_| ())),
+        }
+    }
+}
@@ -1,8 +1,9 @@
 use std::prelude::v1::*;
 
-use crate::{anyhow, Error};
+use crate::Error;
 
 use super::super::{Method, Request, SetupRequest};
+use super::setopt_error;
 use curl::easy::{Easy, List, Transfer};
 
 #[derive(Debug)]
@@ -70,29 +71,30 @@ impl<'easy, 'data, URI: ToString>
             // any other verb needs to use custom_request()
             m => self.custom_request(m.as_str()),
         }
-        .map_err(|e| anyhow!("failed to set curl request method: {:#?}", e))?;
+        .map_err(|e| setopt_error(e, "failed to set curl request method"))?;
 
         self.url(uri.as_str())
-            .map_err(|e| anyhow!("failed to set curl request URL: {:#?}", e))?;
-        let mut headerlist = List::try_from(&r.headers)
-            .map_err(|e| anyhow!("failed to create curl::List from headers: {:#?}", e))?;
+            .map_err(|e| setopt_error(e, "failed to set curl request URL"))?;
+        let mut headerlist = List::try_from(&r.headers)?;
         // libcurl by default adds "Expect: 100-continue" to send bodies, which would break us
         headerlist
             .append("Expect:")
-            .map_err(|e| anyhow!("failed to add node to curl::List: {:#?}", e))?;
-        // don't specify Content-Type for this request (similar to other clients)
-        headerlist
-            .append("Content-Type:")
-            .map_err(|e| anyhow!("failed to add node to curl::List: {:#?}", e))?;
+            .map_err(|e| setopt_error(e, "failed to add node to curl::List"))?;
+        // libcurl would otherwise add its own Content-Type if the header policy did not set one
+        if !r.headers.contains_key("Content-Type") {
+            headerlist
+                .append("Content-Type:")
+                .map_err(|e| setopt_error(e, "failed to add node to curl::List"))?;
+        }
         self.http_headers(headerlist)
-            .map_err(|e| anyhow!("failed to add headers to curl client: {:#?}", e))?;
+            .map_err(|e| setopt_error(e, "failed to add headers to curl client"))?;
 
         Ok(match body {
             Some(_) => {
                 let body = r.parameters.into_inner();
                 // this sets the Content-Length - some servers will misbehave without this
                 self.post_field_size(body.len() as u64)
-                    .map_err(|e| anyhow!("failed to set Content-Length: {:#?}", e))?;
+                    .map_err(|e| setopt_error(e, "failed to set Content-Length"))?;
                 let mut transfer = self.transfer();
 
                 let mut count = 0usize;
@@ -100,7 +102,7 @@ impl<'easy, 'data, URI: ToString>
                     .read_function(move |buf| {
                         Ok(super::copy_data(&mut count, body.as_bytes(), buf))
                     })
-                    .map_err(|e| anyhow!("failed to set curl client read function: {:#?}", e))?;
+                    .map_err(|e| setopt_error(e, "failed to set curl client read function"))?;
 
                 transfer.into()
             }
@@ -108,3 +110,22 @@ impl<'easy, 'data, URI: ToString>
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api_call::Kind, http::Backend, util::fixtures::request};
+
+    #[test]
+    fn setup_request_with_backend() {
+        let backend = Backend::new("backend.example.com").with_prefix("/3scale");
+        let mut easy = Easy::new();
+        // the transfer borrows the handle, so let it go before looking at the URL
+        drop(easy.setup_request(request(Kind::Report), &backend).unwrap());
+
+        assert_eq!(
+            easy.effective_url().unwrap(),
+            Some("https://backend.example.com/3scale/transactions.xml")
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use std::prelude::v1::*;
+
+use super::HeaderMap;
+use crate::{api_call::Kind, http::HeaderError, version::USER_AGENT};
+
+/// Content type of the parameters sent in request bodies.
+pub const FORM_URLENCODED: &str = "application/x-www-form-urlencoded";
+/// Media type of the responses of the 3scale Service Management API.
+pub const ACCEPT_XML: &str = "application/xml";
+
+/// The headers sent along with the requests to each endpoint.
+///
+/// By default requests identify this library in `User-Agent`, ask for XML responses, and
+/// declare bodies as form-urlencoded. Further headers, such as tracing ids or credentials for an
+/// ingress fronting 3scale, can be added to all requests or to those of a given kind, and replace
+/// the values set by the policy for the same header names.
+///
+/// # Examples
+///
+/// ```
+/// use threescalers::{api_call::Kind, http::request::HeaderPolicy};
+///
+/// let policy = HeaderPolicy::new()
+///     .host("backend.example.com")
+///     .unwrap()
+///     .header("X-Request-Id", "abc123")
+///     .unwrap()
+///     .header_for(Kind::Report, "X-Batch", "1")
+///     .unwrap();
+///
+/// let headers = policy.headers_for(Kind::Report, true);
+/// assert_eq!(headers.get("content-type"), Some("application/x-www-form-urlencoded"));
+/// assert_eq!(headers.get("host"), Some("backend.example.com"));
+/// assert_eq!(headers.get("x-batch"), Some("1"));
+///
+/// let headers = policy.headers_for(Kind::Authorize, false);
+/// assert!(!headers.contains_key("content-type"));
+/// assert!(!headers.contains_key("x-batch"));
+/// assert_eq!(headers.get("x-request-id"), Some("abc123"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderPolicy {
+    user_agent: Option<String>,
+    accept: Option<String>,
+    content_type: Option<String>,
+    host: Option<String>,
+    headers: Vec<(Option<Kind>, String, String)>,
+}
+
+impl Default for HeaderPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HeaderPolicy {
+    pub fn new() -> Self {
+        Self {
+            user_agent: Some(USER_AGENT.to_string()),
+            accept: Some(ACCEPT_XML.to_string()),
+            content_type: Some(FORM_URLENCODED.to_string()),
+            host: None,
+            headers: Vec::new(),
+        }
+    }
+
+    /// Sends no headers other than the ones added with `header` and `header_for`, leaving it up
+    /// to the HTTP client to fill in the rest.
+    pub fn none() -> Self {
+        Self {
+            user_agent: None,
+            accept: None,
+            content_type: None,
+            host: None,
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn user_agent<S: Into<String>>(self, user_agent: S) -> Result<Self, HeaderError> {
+        self.set("User-Agent", user_agent, |p, v| p.user_agent = Some(v))
+    }
+
+    pub fn accept<S: Into<String>>(self, accept: S) -> Result<Self, HeaderError> {
+        self.set("Accept", accept, |p, v| p.accept = Some(v))
+    }
+
+    /// Sets the content type of requests with a body.
+    pub fn content_type<S: Into<String>>(self, content_type: S) -> Result<Self, HeaderError> {
+        self.set("Content-Type", content_type, |p, v| {
+            p.content_type = Some(v)
+        })
+    }
+
+    /// Overrides the `Host` header, which clients otherwise derive from the URL. This is useful
+    /// when reaching 3scale through a proxy or an ingress that routes by host name.
+    pub fn host<S: Into<String>>(self, host: S) -> Result<Self, HeaderError> {
+        self.set("Host", host, |p, v| p.host = Some(v))
+    }
+
+    /// Adds a header to the requests of every kind.
+    pub fn header<K: Into<String>, V: Into<String>>(
+        self,
+        name: K,
+        value: V,
+    ) -> Result<Self, HeaderError> {
+        self.add(None, name.into(), value.into())
+    }
+
+    /// Adds a header to the requests of the given kind.
+    pub fn header_for<K: Into<String>, V: Into<String>>(
+        self,
+        kind: Kind,
+        name: K,
+        value: V,
+    ) -> Result<Self, HeaderError> {
+        self.add(Some(kind), name.into(), value.into())
+    }
+
+    fn set<S: Into<String>>(
+        mut self,
+        name: &str,
+        value: S,
+        assign: fn(&mut Self, String),
+    ) -> Result<Self, HeaderError> {
+        let value = value.into();
+        HeaderMap::validate(name, value.as_str())?;
+        assign(&mut self, value);
+        Ok(self)
+    }
+
+    fn add(mut self, kind: Option<Kind>, name: String, value: String) -> Result<Self, HeaderError> {
+        HeaderMap::validate(name.as_str(), value.as_str())?;
+        self.headers.push((kind, name, value));
+        Ok(self)
+    }
+
+    /// Computes the headers of a request of the given kind, which may carry a body.
+    pub fn headers_for(&self, kind: Kind, body: bool) -> HeaderMap {
+        let mut headers = HeaderMap::with_capacity(4 + self.headers.len());
+        self.apply(kind, body, &mut headers);
+        headers
+    }
+
+    // Headers set by the policy replace those already in the map, and extra headers replace both.
+    pub(super) fn apply(&self, kind: Kind, body: bool, headers: &mut HeaderMap) {
+        let policy = [
+            ("User-Agent", self.user_agent.as_ref()),
+            ("Accept", self.accept.as_ref()),
+            ("Content-Type", self.content_type.as_ref().filter(|_| body)),
+            ("Host", self.host.as_ref()),
+        ];
+
+        for (name, value) in policy.iter() {
+            if let Some(value) = value {
+                headers.insert_unchecked(*name, value.as_str());
+            }
+        }
+
+        let extra = self
+            .headers
+            .iter()
+            .filter(|(k, ..)| k.map_or(true, |k| k == kind))
+            .collect::<Vec<_>>();
+
+        for (_, name, _) in extra.iter() {
+            let _ = headers.remove(name);
+        }
+        for (_, name, value) in extra {
+            headers.append_unchecked(name.as_str(), value.as_str());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(headers: &HeaderMap) -> Vec<(&str, &str)> {
+        headers.iter().collect()
+    }
+
+    #[test]
+    fn default_policy() {
+        let policy = HeaderPolicy::new();
+
+        assert_eq!(
+            pairs(&policy.headers_for(Kind::Report, true)),
+            vec![
+                ("User-Agent", USER_AGENT),
+                ("Accept", ACCEPT_XML),
+                ("Content-Type", FORM_URLENCODED),
+            ]
+        );
+        assert_eq!(
+            pairs(&policy.headers_for(Kind::AuthRep, false)),
+            vec![("User-Agent", USER_AGENT), ("Accept", ACCEPT_XML)]
+        );
+        assert!(HeaderPolicy::none()
+            .headers_for(Kind::Report, true)
+            .is_empty());
+    }
+
+    #[test]
+    fn extra_headers_replace_policy_headers() {
+        let policy = HeaderPolicy::none()
+            .accept("text/xml")
+            .unwrap()
+            .header("accept", "application/xml")
+            .unwrap()
+            .header("X-Tag", "a")
+            .unwrap()
+            .header_for(Kind::Authorize, "x-tag", "b")
+            .unwrap()
+            .header_for(Kind::Report, "X-Report", "1")
+            .unwrap();
+
+        assert_eq!(
+            pairs(&policy.headers_for(Kind::Authorize, false)),
+            vec![
+                ("accept", "application/xml"),
+                ("X-Tag", "a"),
+                ("X-Tag", "b")
+            ]
+        );
+        assert_eq!(
+            pairs(&policy.headers_for(Kind::AuthRep, false)),
+            vec![("accept", "application/xml"), ("X-Tag", "a")]
+        );
+
+        let mut headers = HeaderMap::new();
+        headers
+            .insert("3scale-options", "rejection_reason_header=1")
+            .unwrap();
+        policy.apply(Kind::Report, true, &mut headers);
+        assert_eq!(headers.len(), 4);
+        assert_eq!(headers.get("x-report"), Some("1"));
+        assert!(headers.contains_key("3scale-options"));
+    }
+
+    #[test]
+    fn invalid_headers_are_rejected() {
+        assert_eq!(
+            HeaderPolicy::new().host("a\r\nb"),
+            Err(HeaderError::InvalidValue("Host".to_string()))
+        );
+        assert_eq!(
+            HeaderPolicy::new().header_for(Kind::Report, "X Bad", "1"),
+            Err(HeaderError::InvalidName("X Bad".to_string()))
+        );
+    }
+}
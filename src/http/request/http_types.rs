@@ -1,7 +1,8 @@
 use std::prelude::v1::*;
 
 use super::{HeaderMap, Method, Request};
-use crate::{anyhow, api_call::ApiCall, version::*, Error};
+use crate::http::{Backend, HeaderError};
+use crate::{api_call::ApiCall, version::*, ClientError, ClientErrorKind, Error};
 use core::convert::TryFrom;
 use http_types::{
     header::{HeaderName, HeaderValue},
@@ -24,6 +25,30 @@ impl From<Method> for HTTPMethod {
     }
 }
 
+impl TryFrom<&HTTPMethod> for Method {
+    type Error = Error;
+
+    fn try_from(m: &HTTPMethod) -> Result<Self, Self::Error> {
+        Ok(match *m {
+            HTTPMethod::GET => Self::GET,
+            HTTPMethod::POST => Self::POST,
+            HTTPMethod::PUT => Self::PUT,
+            HTTPMethod::DELETE => Self::DELETE,
+            HTTPMethod::PATCH => Self::PATCH,
+            HTTPMethod::HEAD => Self::HEAD,
+            _ => return Err(Error::InvalidMethod(m.to_string())),
+        })
+    }
+}
+
+impl TryFrom<HTTPMethod> for Method {
+    type Error = Error;
+
+    fn try_from(m: HTTPMethod) -> Result<Self, Self::Error> {
+        Self::try_from(&m)
+    }
+}
+
 // internal - probably best to use some combination of iterators using Extend?
 trait FillFrom {
     type Error;
@@ -32,17 +57,17 @@ trait FillFrom {
 }
 
 impl FillFrom for HTTPHeaderMap {
-    type Error = Error;
+    type Error = HeaderError;
 
     fn fill_from(&mut self, hm: &HeaderMap) -> Result<(), Self::Error> {
         use core::str::FromStr;
 
         let it = hm.iter();
         for (key, value) in it {
-            let key = HeaderName::from_str(key.as_str())
-                .map_err(|e| anyhow!("failed validation of header name: {:#?}", e))?;
+            let key =
+                HeaderName::from_str(key).map_err(|_| HeaderError::InvalidName(key.to_owned()))?;
             let value = HeaderValue::try_from(value)
-                .map_err(|e| anyhow!("failed validation of header value: {:#?}", e))?;
+                .map_err(|_| HeaderError::InvalidValue(key.to_string()))?;
             self.append(key, value);
         }
 
@@ -56,8 +81,7 @@ impl TryFrom<HeaderMap> for HTTPHeaderMap {
     fn try_from(hm: HeaderMap) -> Result<Self, Self::Error> {
         let mut map = HTTPHeaderMap::with_capacity(hm.len());
 
-        map.fill_from(&hm)
-            .map_err(|e| anyhow!("failed to convert header map to http's HeaderMap: {:#?}", e))?;
+        map.fill_from(&hm)?;
 
         Ok(map)
     }
@@ -71,18 +95,22 @@ impl TryFrom<Request> for HTTPRequest<String> {
         let body = body.unwrap_or("").to_owned();
         let rb = HTTPRequest::builder();
 
-        let mut rb = rb
-            .header("User-Agent", USER_AGENT)
-            .method(HTTPMethod::from(r.method))
-            .uri(uri.as_ref());
+        let mut rb = rb.method(HTTPMethod::from(r.method)).uri(uri.as_ref());
+
+        // only add our user agent if the request does not specify one, to avoid duplicates
+        if !r.headers.contains_key("User-Agent") {
+            rb = rb.header("User-Agent", USER_AGENT);
+        }
 
         let map = rb.headers_mut().unwrap();
 
-        map.fill_from(&r.headers)
-            .map_err(|e| anyhow!("failed to attach headers to request: {:#?}", e))?;
+        map.fill_from(&r.headers)?;
 
-        rb.body(body)
-            .map_err(|e| anyhow!("failed to assign body to request: {:#?}", e))
+        rb.body(body).map_err(|e| {
+            ClientError::new(ClientErrorKind::Request, "failed to build the request")
+                .with_source(e)
+                .into()
+        })
     }
 }
 
@@ -100,6 +128,78 @@ use crate::util::Never;
 impl SetupRequest<'_, Never, Result<HTTPRequest<String>, Error>> for Builder {
     fn setup_request(&mut self, r: Request, _params: Never) -> Result<HTTPRequest<String>, Error> {
         HTTPRequest::try_from(r)
-            .map_err(|e| anyhow!("failed to convert request to http's Request: {:#?}", e))
+    }
+}
+
+// Builds a request with an absolute URI, with the host and any path prefix of the backend.
+impl SetupRequest<'_, &Backend, Result<HTTPRequest<String>, Error>> for Builder {
+    fn setup_request(
+        &mut self,
+        r: Request,
+        backend: &Backend,
+    ) -> Result<HTTPRequest<String>, Error> {
+        let uri = backend.uri_for(&r).parse().map_err(|e| {
+            ClientError::new(ClientErrorKind::Request, "failed to parse the backend URI")
+                .with_source(e)
+        })?;
+        let mut request = HTTPRequest::try_from(r)?;
+        *request.uri_mut() = uri;
+
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api_call::Kind,
+        util::fixtures::{request, with_apicall},
+    };
+
+    #[test]
+    fn user_agent_is_not_duplicated() {
+        let authorize =
+            with_apicall(Kind::Authorize, |apicall| HTTPRequest::try_from(apicall)).unwrap();
+        let user_agents = authorize.headers().get_all("user-agent").iter().count();
+        assert_eq!(user_agents, 1);
+
+        let mut request = request(Kind::Authorize);
+        request.headers.insert("user-agent", "custom").unwrap();
+        let request = HTTPRequest::try_from(request).unwrap();
+        assert_eq!(
+            request
+                .headers()
+                .get_all("user-agent")
+                .iter()
+                .collect::<Vec<_>>(),
+            vec!["custom"]
+        );
+    }
+
+    #[test]
+    fn invalid_headers_are_typed_errors() {
+        let mut headers = HeaderMap::new();
+        headers.insert_unchecked("X Bad", "v");
+
+        match HTTPHeaderMap::try_from(headers) {
+            Err(Error::InvalidHeader(HeaderError::InvalidName(name))) => assert_eq!(name, "X Bad"),
+            r => core::panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn setup_request_with_backend() {
+        let backend = Backend::new("backend.example.com").with_prefix("/3scale");
+
+        let request = Builder::new()
+            .setup_request(request(Kind::Report), &backend)
+            .unwrap();
+        assert_eq!(
+            request.uri().to_string(),
+            "https://backend.example.com/3scale/transactions.xml"
+        );
+        assert_eq!(request.method(), HTTPMethod::POST);
+        assert!(request.body().contains("service_id=a_service"));
     }
 }
@@ -0,0 +1,101 @@
+use std::prelude::v1::*;
+
+use core::{convert::TryFrom, str::FromStr};
+
+use ::hyper::{Request as HyperRequest, Response as HyperResponse};
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper_util::client::legacy::{connect::Connect, Client};
+
+use super::{Request, SetupRequest};
+use crate::{http::Backend, response::Authorization, ClientError, ClientErrorKind, Error};
+
+/// The body type of the requests sent through hyper.
+pub type Body = Full<Bytes>;
+
+/// Builds a hyper request with an absolute URI, as required by hyper's client.
+pub fn build_request(r: Request, backend: &Backend) -> Result<HyperRequest<Body>, Error> {
+    let uri = backend.uri_for(&r).parse::<::hyper::Uri>().map_err(|e| {
+        ClientError::new(ClientErrorKind::Request, "failed to parse the backend URI").with_source(e)
+    })?;
+    let mut request = HyperRequest::<String>::try_from(r)?;
+    *request.uri_mut() = uri;
+
+    Ok(request.map(|body| Full::new(Bytes::from(body))))
+}
+
+// hyper's client needs absolute URIs, so requests are always set up with a backend.
+impl<C> SetupRequest<'_, &Backend, Result<HyperRequest<Body>, Error>> for Client<C, Body>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    fn setup_request(
+        &mut self,
+        r: Request,
+        backend: &Backend,
+    ) -> Result<HyperRequest<Body>, Error> {
+        build_request(r, backend)
+    }
+}
+
+/// Sends a request and collects the whole body of its response.
+pub async fn send<C>(
+    client: &Client<C, Body>,
+    request: HyperRequest<Body>,
+) -> Result<HyperResponse<Bytes>, Error>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    let response = client.request(request).await.map_err(|e| {
+        // hyper has no timeouts of its own, those are left to the caller
+        let kind = if e.is_connect() {
+            ClientErrorKind::Connect
+        } else {
+            ClientErrorKind::Transport
+        };
+        ClientError::new(kind, "failed to send request").with_source(e)
+    })?;
+    let (parts, body) = response.into_parts();
+    let body = body
+        .collect()
+        .await
+        .map_err(|e| {
+            ClientError::new(ClientErrorKind::Transport, "failed to read response body")
+                .with_source(e)
+        })?
+        .to_bytes();
+
+    Ok(HyperResponse::from_parts(parts, body))
+}
+
+/// Sends an authorize or authrep request to a backend and parses its response.
+///
+/// Denied authorizations are answered with a client error status and are still returned as an
+/// `Authorization`, so only responses without a parseable body are errors.
+pub async fn authorize<C>(
+    client: &Client<C, Body>,
+    request: Request,
+    backend: &Backend,
+) -> Result<Authorization, Error>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    let request = build_request(request, backend)?;
+    let response = send(client, request).await?;
+    let status = response.status();
+    let body = core::str::from_utf8(response.body()).map_err(|e| {
+        ClientError::new(
+            ClientErrorKind::Response,
+            "response body is not valid UTF-8",
+        )
+        .with_source(e)
+    })?;
+
+    // bodies of server errors are most likely not coming from 3scale
+    Authorization::from_str(body).map_err(|e| match e {
+        Error::Xml(e) if !status.is_success() => {
+            ClientError::unexpected_status(status.as_u16(), e).into()
+        }
+        e => e,
+    })
+}
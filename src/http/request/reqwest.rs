@@ -1,7 +1,7 @@
 use std::prelude::v1::*;
 
 use super::{Request, SetupRequest};
-use crate::{anyhow, Error};
+use crate::Error;
 
 macro_rules! reqwest_impl {
     { $C:ty, $B:ty } => {
@@ -18,8 +18,7 @@ macro_rules! reqwest_impl {
                 let uri = uri_base.to_string() + uri.as_ref();
 
                 let rb = self.request(r.method.into(), uri.as_str())
-                    .headers(r.headers.try_into()
-                             .map_err(|e| anyhow!("failed to add headers to reqwest: {:#?}", e))?);
+                    .headers(r.headers.try_into()?);
 
                 Ok(match body {
                     // when there is a body just consume it from the request's
@@ -36,3 +35,45 @@ macro_rules! reqwest_impl {
 reqwest_impl!(reqwest::Client, reqwest::RequestBuilder);
 #[cfg(feature = "reqwest-sync")]
 reqwest_impl!(reqwest::blocking::Client, reqwest::blocking::RequestBuilder);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api_call::Kind, http::Backend, util::fixtures::request};
+
+    fn backend() -> Backend {
+        Backend::new("backend.example.com").with_prefix("/3scale")
+    }
+
+    #[cfg(feature = "reqwest-async")]
+    #[test]
+    fn setup_async_request_with_backend() {
+        let request = reqwest::Client::new()
+            .setup_request(request(Kind::Authorize), &backend())
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(request.method(), reqwest::Method::GET);
+        assert!(request
+            .url()
+            .as_str()
+            .starts_with("https://backend.example.com/3scale/transactions/authorize.xml?"));
+    }
+
+    #[cfg(feature = "reqwest-sync")]
+    #[test]
+    fn setup_blocking_request_with_backend() {
+        let request = reqwest::blocking::Client::new()
+            .setup_request(request(Kind::Report), &backend())
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(request.method(), reqwest::Method::POST);
+        assert_eq!(
+            request.url().as_str(),
+            "https://backend.example.com/3scale/transactions.xml"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use std::prelude::v1::*;
+
+use core::{convert::TryFrom, str::FromStr};
+
+use super::{Request, SetupRequest};
+use crate::{response::Authorization, ClientError, ClientErrorKind, Error};
+
+/// A ureq request along with its body, ready to be sent.
+///
+/// ureq only takes the body of a request when sending it, so it is kept here until then.
+#[derive(Debug)]
+pub struct UreqRequest {
+    request: ureq::Request,
+    body: Option<String>,
+}
+
+impl UreqRequest {
+    pub fn request(&self) -> &ureq::Request {
+        &self.request
+    }
+
+    pub fn body(&self) -> Option<&str> {
+        self.body.as_deref()
+    }
+
+    pub fn into_parts(self) -> (ureq::Request, Option<String>) {
+        (self.request, self.body)
+    }
+
+    // ureq's error is large, but returning it as is keeps the same interface as ureq's calls
+    #[allow(clippy::result_large_err)]
+    pub fn call(self) -> Result<ureq::Response, ureq::Error> {
+        match self.body {
+            Some(body) => self.request.send_string(body.as_str()),
+            None => self.request.call(),
+        }
+    }
+
+    /// Sends an authorize or authrep request and parses its response.
+    ///
+    /// Denied authorizations are answered with a client error status, which ureq reports as an
+    /// error, but they are still returned as an `Authorization`.
+    pub fn authorize(self) -> Result<Authorization, Error> {
+        match self.call() {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => {
+                Authorization::try_from(response)
+            }
+            Err(e) => Err(client_error(e).into()),
+        }
+    }
+}
+
+// Error statuses carry a response and are handled by the callers before getting here.
+pub(crate) fn client_error(e: ureq::Error) -> ClientError {
+    use ureq::ErrorKind::*;
+
+    let kind = match e.kind() {
+        InvalidUrl | UnknownScheme | InsecureRequestHttpsOnly | InvalidProxyUrl => {
+            ClientErrorKind::Request
+        }
+        Dns | ConnectionFailed | ProxyConnect => ClientErrorKind::Connect,
+        Io if is_timeout(&e) => ClientErrorKind::Timeout,
+        _ => ClientErrorKind::Transport,
+    };
+
+    ClientError::new(kind, "failed to send request").with_source(e)
+}
+
+// ureq reports timeouts as I/O errors.
+fn is_timeout(e: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(e);
+    while let Some(e) = source {
+        if let Some(e) = e.downcast_ref::<std::io::Error>() {
+            return matches!(
+                e.kind(),
+                std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+            );
+        }
+        source = e.source();
+    }
+    false
+}
+
+pub(crate) fn body_error(e: std::io::Error) -> ClientError {
+    let kind = if is_timeout(&e) {
+        ClientErrorKind::Timeout
+    } else {
+        ClientErrorKind::Transport
+    };
+
+    ClientError::new(kind, "failed to read response body").with_source(e)
+}
+
+// Like reqwest, ureq won't build a request without a base URI, ie:
+//
+// https://a_host
+//
+impl<URI: ToString> SetupRequest<'_, URI, Result<UreqRequest, Error>> for ureq::Agent {
+    fn setup_request(&mut self, r: Request, params: URI) -> Result<UreqRequest, Error> {
+        let (uri, body) = r.parameters.uri_and_body(r.path);
+        let uri_base = params;
+        let uri = uri_base.to_string() + uri.as_ref();
+
+        // ureq replaces previous values of most headers when setting them, so send the values of
+        // each header as a single comma-separated list.
+        let mut request = self.request(r.method.as_str(), uri.as_str());
+        for (name, _) in r.headers.iter() {
+            if !request.has(name) {
+                let values = r.headers.get_all(name).collect::<Vec<_>>();
+                request = request.set(name, values.join(", ").as_str());
+            }
+        }
+
+        Ok(UreqRequest {
+            request,
+            body: match body {
+                // when there is a body just consume it from the request's
+                // parameters to avoid cloning it unnecessarily.
+                Some(_) => Some(r.parameters.into_inner()),
+                _ => None,
+            },
+        })
+    }
+}
+
+impl TryFrom<ureq::Response> for Authorization {
+    type Error = Error;
+
+    fn try_from(response: ureq::Response) -> Result<Self, Error> {
+        let status = response.status();
+        let body = response.into_string().map_err(body_error)?;
+
+        Authorization::from_str(body.as_str()).map_err(|e| match e {
+            Error::Xml(e) if !(200..300).contains(&status) => {
+                ClientError::unexpected_status(status, e).into()
+            }
+            e => e,
+        })
+    }
+}
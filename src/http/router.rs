@@ -0,0 +1,328 @@
+// Routing of requests to 3scale services in gateways fronting several of them.
+//
+// Services are selected by the host the request was sent to and, optionally, by a path prefix
+// as with 3scale backend API paths. The prefix is stripped from the path before matching the
+// service's mapping rules, since these are written relative to it.
+use std::prelude::v1::*;
+
+use crate::{
+    http::mapping_rule::{Method, Normalization, RuleSet},
+    service::Service,
+};
+
+/// A service reachable through the router, with the mapping rules that apply to it.
+#[derive(Debug, Clone)]
+pub struct Route {
+    service: Service,
+    rules: RuleSet,
+    hosts: Vec<String>,
+    prefix: String,
+}
+
+impl Route {
+    /// Creates a route that matches any host and path until restricted with `host` and
+    /// `prefix`.
+    pub fn new(service: Service, rules: RuleSet) -> Self {
+        Self {
+            service,
+            rules,
+            hosts: Vec::new(),
+            prefix: String::new(),
+        }
+    }
+
+    /// Adds a host name this route matches. A leading `*.` matches any subdomain.
+    pub fn host<S: AsRef<str>>(mut self, host: S) -> Self {
+        self.hosts.push(host.as_ref().to_ascii_lowercase());
+        self
+    }
+
+    /// Restricts the route to paths under the given prefix, which is matched on whole path
+    /// segments, so that `/api` matches `/api/products` but not `/apis`.
+    pub fn prefix<S: AsRef<str>>(mut self, prefix: S) -> Self {
+        self.prefix = prefix.as_ref().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn service(&self) -> &Service {
+        &self.service
+    }
+
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    pub fn hosts(&self) -> &[String] {
+        self.hosts.as_slice()
+    }
+
+    pub fn path_prefix(&self) -> &str {
+        self.prefix.as_str()
+    }
+
+    // Higher is more specific: exact hosts over wildcards over routes matching any host.
+    fn host_specificity(&self, host: &str) -> Option<usize> {
+        if self.hosts.is_empty() {
+            return Some(0);
+        }
+
+        self.hosts
+            .iter()
+            .filter_map(|pattern| match pattern.strip_prefix("*.") {
+                None if pattern == host => Some(usize::MAX),
+                Some(domain)
+                    if host.len() > domain.len() + 1
+                        && host.ends_with(domain)
+                        && host[..host.len() - domain.len()].ends_with('.') =>
+                {
+                    Some(1 + domain.len())
+                }
+                _ => None,
+            })
+            .max()
+    }
+
+    // Returns the path relative to the prefix, or None if the path is not under it.
+    fn strip_prefix<'p>(&self, path: &'p str) -> Option<&'p str> {
+        let rest = path.strip_prefix(self.prefix.as_str())?;
+
+        if rest.is_empty() || rest.starts_with('/') {
+            Some(rest)
+        } else {
+            None
+        }
+    }
+}
+
+/// The result of routing a request: the route it belongs to and its path and query string
+/// relative to the route's prefix.
+#[derive(Debug, Clone)]
+pub struct Routed<'r> {
+    route: &'r Route,
+    path_qs: String,
+}
+
+impl<'r> Routed<'r> {
+    pub fn route(&self) -> &'r Route {
+        self.route
+    }
+
+    pub fn service(&self) -> &'r Service {
+        &self.route.service
+    }
+
+    /// The normalized path and query string with the prefix removed.
+    pub fn path_qs(&self) -> &str {
+        self.path_qs.as_str()
+    }
+
+    /// Computes the usage the request should report according to the route's rules.
+    pub fn usage(&self, method: &Method) -> Vec<(&'r str, u64)> {
+        self.route.rules.usage(method, self.path_qs.as_str())
+    }
+}
+
+/// Selects the service a request belongs to from its host and path.
+///
+/// The route with the most specific host wins, and among those, the one with the longest
+/// matching prefix. Ties are resolved in favor of the route added first.
+///
+/// # Examples
+///
+/// ```
+/// use threescalers::{
+///     credentials::Credentials,
+///     http::{
+///         mapping_rule::{MappingRule, Method, RestRule, RuleSet},
+///         router::{Route, Router},
+///     },
+///     service::Service,
+/// };
+///
+/// let rules = RuleSet::from(vec![MappingRule::new(
+///     RestRule::new("GET", "/products/{id}").unwrap(),
+///     "products",
+///     1,
+///     false,
+/// )]);
+/// let service = Service::new("my_service", Credentials::from_token("my_token"));
+/// let router = Router::new().route(
+///     Route::new(service, rules)
+///         .host("api.example.com")
+///         .prefix("/shop"),
+/// );
+///
+/// let routed = router.resolve("API.example.com:443", "/shop/products/1").unwrap();
+/// assert_eq!(routed.path_qs(), "/products/1");
+/// assert_eq!(routed.usage(&Method::GET), vec![("products", 1)]);
+///
+/// assert!(router.resolve("api.example.com", "/shopping").is_none());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    normalization: Normalization,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn route(mut self, route: Route) -> Self {
+        self.routes.push(route);
+        self
+    }
+
+    /// Sets how paths are normalized before looking for a prefix, so that ie. `/api/../admin`
+    /// is not routed as being under `/api`. Uses the default normalization otherwise.
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    pub fn routes(&self) -> &[Route] {
+        self.routes.as_slice()
+    }
+
+    /// Finds the route for a request given the value of its `Host` header, which can include a
+    /// port, and its path and query string.
+    pub fn resolve<H: AsRef<str>, P: AsRef<str>>(&self, host: H, path_qs: P) -> Option<Routed<'_>> {
+        let host = normalize_host(host.as_ref());
+        let path_qs = path_qs.as_ref();
+        let (path, qs) = path_qs.find('?').map_or((path_qs, None), |idx| {
+            (&path_qs[..idx], Some(&path_qs[idx..]))
+        });
+        let path = self.normalization.path(path);
+
+        let (route, relative) = self
+            .routes
+            .iter()
+            .filter_map(|route| {
+                let specificity = route.host_specificity(host.as_str())?;
+                let relative = route.strip_prefix(path.as_str())?;
+
+                Some(((specificity, route.prefix.len()), route, relative))
+            })
+            // max_by_key returns the last maximum, so reverse to favor earlier routes
+            .rev()
+            .max_by_key(|(key, _, _)| *key)
+            .map(|(_, route, relative)| (route, relative))?;
+
+        let mut relative = if relative.is_empty() {
+            String::from("/")
+        } else {
+            relative.to_string()
+        };
+        relative.push_str(qs.unwrap_or(""));
+
+        Some(Routed {
+            route,
+            path_qs: relative,
+        })
+    }
+}
+
+// Host names are case insensitive, and the Host header can include a port.
+fn normalize_host(host: &str) -> String {
+    let host = match host.rfind(':') {
+        // keep IPv6 literals intact unless a port follows the closing bracket
+        Some(idx) if !host[idx..].contains(']') => &host[..idx],
+        _ => host,
+    };
+
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        credentials::Credentials,
+        http::mapping_rule::{MappingRule, RestRule},
+    };
+
+    fn route(service_id: &str, pattern: &str) -> Route {
+        let rules = RuleSet::from(vec![MappingRule::new(
+            RestRule::new("ANY", pattern).unwrap(),
+            service_id,
+            1,
+            false,
+        )]);
+
+        Route::new(
+            Service::new(service_id, Credentials::from_token("token")),
+            rules,
+        )
+    }
+
+    fn resolved<'r>(router: &'r Router, host: &str, path: &str) -> Option<(&'r str, String)> {
+        router.resolve(host, path).map(|routed| {
+            let usage = routed.usage(&Method::GET);
+            (
+                usage.first().map_or("", |(m, _)| *m),
+                routed.path_qs().to_string(),
+            )
+        })
+    }
+
+    #[test]
+    fn route_by_host_and_prefix() {
+        let router = Router::new()
+            .route(route("fallback", "/"))
+            .route(route("wildcard", "/").host("*.example.com"))
+            .route(
+                route("api", "/")
+                    .host("api.example.com")
+                    .host("api.example.org"),
+            )
+            .route(route("shop", "/").host("api.example.com").prefix("/shop/"))
+            .route(
+                route("cart", "/")
+                    .host("api.example.com")
+                    .prefix("/shop/cart"),
+            );
+
+        let cases = [
+            ("other.net", "/shop", Some(("fallback", "/shop"))),
+            ("www.example.com", "/", Some(("wildcard", "/"))),
+            ("example.com", "/", Some(("fallback", "/"))),
+            ("API.example.org:8080", "/x?a=1", Some(("api", "/x?a=1"))),
+            ("api.example.com", "/shop", Some(("shop", "/"))),
+            ("api.example.com", "/shop?q=1", Some(("shop", "/?q=1"))),
+            ("api.example.com", "/shop/cart/1", Some(("cart", "/1"))),
+            ("api.example.com", "/shopping", Some(("api", "/shopping"))),
+            ("api.example.com", "/shop/../admin", Some(("api", "/admin"))),
+            ("api.example.com", "//shop//%63art", Some(("cart", "/"))),
+        ];
+
+        for (host, path, expected) in cases {
+            assert_eq!(
+                resolved(&router, host, path),
+                expected.map(|(m, p)| (m, p.to_string())),
+                "{} {}",
+                host,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn unmatched_requests() {
+        let router = Router::new()
+            .route(route("first", "/").host("api.example.com"))
+            .route(route("second", "/").host("api.example.com"))
+            .route(route("v1", "/").host("[::1]").prefix("/v1"));
+
+        assert!(router.resolve("other.example.com", "/").is_none());
+        assert!(router.resolve("[::1]:8080", "/v2").is_none());
+        assert_eq!(
+            resolved(&router, "[::1]:8080", "/v1/a"),
+            Some(("v1", "/a".to_string()))
+        );
+        assert_eq!(
+            resolved(&router, "api.example.com.", "/"),
+            Some(("first", "/".to_string()))
+        );
+    }
+}
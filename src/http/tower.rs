@@ -0,0 +1,424 @@
+// Tower middleware authorizing incoming requests with 3scale before forwarding them.
+//
+// Credentials are extracted from each request and its usage is computed from the mapping rules,
+// then an authrep call is sent through a transport, which is itself a tower service taking our
+// requests to 3scale and returning the parsed authorizations. Requests are only forwarded to the
+// inner service once authorized, and rejected with a response explaining why otherwise.
+use std::prelude::v1::*;
+
+use core::{
+    future::{poll_fn, Future},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use std::sync::Arc;
+
+use http_types::{
+    header::CONTENT_TYPE, HeaderValue, Request as HTTPRequest, Response as HTTPResponse, StatusCode,
+};
+use tower_layer::Layer;
+use tower_service::Service as TowerService;
+
+#[cfg(feature = "hyper")]
+use crate::http::{
+    request::hyper::{authorize, Body},
+    Backend,
+};
+#[cfg(feature = "hyper")]
+use hyper_util::client::legacy::{connect::Connect, Client};
+
+use crate::{
+    api_call::{ApiCall, Kind},
+    http::{
+        extractor::Extractor,
+        mapping_rule::{Method, RuleSet},
+        request::HeaderPolicy,
+        Request,
+    },
+    response::{Authorization, AuthorizationStatus},
+    service::Service,
+    transaction::Transaction,
+    usage::Usage,
+    Error,
+};
+
+/// The reason given by 3scale when an application has gone over its limits.
+pub const LIMITS_EXCEEDED: &str = "usage limits are exceeded";
+
+#[derive(Debug, Clone)]
+struct Config {
+    service: Service,
+    extractor: Extractor,
+    rules: RuleSet,
+    header_policy: HeaderPolicy,
+}
+
+type Rejection = (StatusCode, String);
+
+impl Config {
+    // Builds the authrep request for an incoming request, or the reason to reject it outright.
+    fn authrep_request<B>(&self, request: &HTTPRequest<B>) -> Result<Request, Rejection> {
+        let (app, user) = self
+            .extractor
+            .extract_from_request(request)
+            .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))?;
+
+        let path_qs = request
+            .uri()
+            .path_and_query()
+            .map_or("/", http_types::uri::PathAndQuery::as_str);
+        let usage = self.rules.usage(&Method::from(request.method()), path_qs);
+        if usage.is_empty() {
+            return Err((StatusCode::NOT_FOUND, "no mapping rule matched".to_string()));
+        }
+
+        let usage = usage
+            .into_iter()
+            .map(|(metric, delta)| (metric, delta.to_string()))
+            .collect::<Vec<_>>();
+        let usage = Usage::new(usage.as_slice());
+        let txns = [Transaction::new(&app, user.as_ref(), Some(&usage), None)];
+        let apicall = ApiCall::new(Kind::AuthRep, &self.service, &txns, None);
+
+        Ok(Request::with_policy(&apicall, &self.header_policy))
+    }
+}
+
+// Denials for going over the limits are told apart so that clients can back off.
+fn denial(status: &AuthorizationStatus) -> Rejection {
+    let reason = status.reason().unwrap_or("unspecified reason");
+    let code = if reason == LIMITS_EXCEEDED {
+        StatusCode::TOO_MANY_REQUESTS
+    } else {
+        StatusCode::FORBIDDEN
+    };
+
+    (code, reason.to_string())
+}
+
+fn response<B: From<String>>((status, message): Rejection) -> HTTPResponse<B> {
+    let mut response = HTTPResponse::new(B::from(message));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    response
+}
+
+/// A `Layer` wrapping services with `AuthRep`.
+///
+/// The transport can be any service sending requests to 3scale and returning their parsed
+/// authorizations, such as `HyperTransport`.
+#[derive(Debug, Clone)]
+pub struct AuthRepLayer<T> {
+    config: Arc<Config>,
+    transport: T,
+}
+
+impl<T> AuthRepLayer<T> {
+    pub fn new(service: Service, extractor: Extractor, rules: RuleSet, transport: T) -> Self {
+        Self {
+            config: Arc::new(Config {
+                service,
+                extractor,
+                rules,
+                header_policy: HeaderPolicy::default(),
+            }),
+            transport,
+        }
+    }
+
+    /// Sets the headers sent along with the authrep calls.
+    pub fn header_policy(mut self, header_policy: HeaderPolicy) -> Self {
+        Arc::make_mut(&mut self.config).header_policy = header_policy;
+        self
+    }
+}
+
+impl<S, T: Clone> Layer<S> for AuthRepLayer<T> {
+    type Service = AuthRep<S, T>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthRep {
+            inner,
+            config: self.config.clone(),
+            transport: self.transport.clone(),
+        }
+    }
+}
+
+/// A service authorizing and reporting each request with 3scale before forwarding it.
+///
+/// Requests are rejected with:
+///
+/// * 401 if they carry no credentials.
+/// * 404 if they match no mapping rule.
+/// * 403 if 3scale denies them, or 429 if the application went over its limits.
+/// * 503 if 3scale could not be reached.
+#[derive(Debug, Clone)]
+pub struct AuthRep<S, T> {
+    inner: S,
+    config: Arc<Config>,
+    transport: T,
+}
+
+impl<S, T, B, ResBody> TowerService<HTTPRequest<B>> for AuthRep<S, T>
+where
+    S: TowerService<HTTPRequest<B>, Response = HTTPResponse<ResBody>> + Clone + Send + 'static,
+    S::Error: Send,
+    S::Future: Send,
+    T: TowerService<Request, Response = Authorization, Error = Error> + Clone + Send + 'static,
+    T::Future: Send,
+    B: Send + 'static,
+    ResBody: From<String> + Send + 'static,
+{
+    type Response = HTTPResponse<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: HTTPRequest<B>) -> Self::Future {
+        // the inner service is the one that was driven to readiness, so keep it for this request
+        let clone = self.inner.clone();
+        let mut inner = core::mem::replace(&mut self.inner, clone);
+        let mut transport = self.transport.clone();
+        let authrep = self.config.authrep_request(&request);
+
+        Box::pin(async move {
+            let authrep = match authrep {
+                Ok(authrep) => authrep,
+                Err(rejection) => return Ok(response(rejection)),
+            };
+
+            let unavailable = |_: Error| {
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "authorization service unavailable".to_string(),
+                )
+            };
+            let authorization = match poll_fn(|cx| transport.poll_ready(cx)).await {
+                Ok(()) => transport.call(authrep).await.map_err(unavailable),
+                Err(e) => Err(unavailable(e)),
+            };
+
+            match authorization {
+                Ok(Authorization::Status(status)) if status.is_authorized() => {
+                    inner.call(request).await
+                }
+                Ok(Authorization::Status(status)) => Ok(response(denial(&status))),
+                Ok(Authorization::Error(error)) => Ok(response((
+                    StatusCode::FORBIDDEN,
+                    error.description().to_string(),
+                ))),
+                Err(rejection) => Ok(response(rejection)),
+            }
+        })
+    }
+}
+
+/// A transport sending requests to a 3scale backend with a hyper client.
+#[cfg(feature = "hyper")]
+#[derive(Debug, Clone)]
+pub struct HyperTransport<C> {
+    client: Client<C, Body>,
+    backend: Arc<Backend>,
+}
+
+#[cfg(feature = "hyper")]
+impl<C> HyperTransport<C> {
+    pub fn new(client: Client<C, Body>, backend: Backend) -> Self {
+        Self {
+            client,
+            backend: Arc::new(backend),
+        }
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl<C> TowerService<Request> for HyperTransport<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    type Response = Authorization;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Authorization, Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let client = self.client.clone();
+        let backend = self.backend.clone();
+
+        Box::pin(async move { authorize(&client, request, &backend).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http::{
+            extractor::{BackendVersion, Location},
+            mapping_rule::{MappingRule, RestRule},
+        },
+        util::fixtures,
+    };
+    use core::{convert::Infallible, future::Ready, str::FromStr};
+
+    // Answers every request with the same authorization, recording the requests it gets.
+    #[derive(Clone)]
+    struct Mock {
+        response: Option<String>,
+        requests: Arc<std::sync::Mutex<Vec<Request>>>,
+    }
+
+    impl TowerService<Request> for Mock {
+        type Response = Authorization;
+        type Error = Error;
+        type Future = Ready<Result<Authorization, Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request) -> Self::Future {
+            self.requests.lock().unwrap().push(request);
+            core::future::ready(
+                self.response
+                    .as_deref()
+                    .ok_or_else(|| {
+                        crate::ClientError::new(
+                            crate::ClientErrorKind::Connect,
+                            "connection refused",
+                        )
+                        .into()
+                    })
+                    .and_then(Authorization::from_str),
+            )
+        }
+    }
+
+    #[derive(Clone)]
+    struct Echo;
+
+    impl TowerService<HTTPRequest<()>> for Echo {
+        type Response = HTTPResponse<String>;
+        type Error = Infallible;
+        type Future = Ready<Result<Self::Response, Infallible>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: HTTPRequest<()>) -> Self::Future {
+            core::future::ready(Ok(HTTPResponse::new(request.uri().to_string())))
+        }
+    }
+
+    fn status_xml(authorized: bool, reason: &str) -> String {
+        format!(
+            "<status><authorized>{}</authorized><reason>{}</reason><plan>Basic</plan></status>",
+            authorized, reason
+        )
+    }
+
+    async fn send(response: Option<String>, uri: &str) -> (HTTPResponse<String>, Vec<Request>) {
+        let rules = RuleSet::from(vec![MappingRule::new(
+            RestRule::new("GET", "/products").unwrap(),
+            "products",
+            2,
+            false,
+        )]);
+        let mock = Mock {
+            response,
+            requests: Default::default(),
+        };
+        let layer = AuthRepLayer::new(
+            fixtures::service(),
+            Extractor::new(BackendVersion::UserKey, Location::Query),
+            rules,
+            mock.clone(),
+        );
+        let mut service = layer.layer(Echo);
+
+        poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+        let request = HTTPRequest::get(uri).body(()).unwrap();
+        let response = service.call(request).await.unwrap();
+        let requests = mock.requests.lock().unwrap().clone();
+
+        (response, requests)
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn forwards_authorized_requests() {
+        let authorized = status_xml(true, "");
+        let (response, requests) = send(Some(authorized), "/products?user_key=a_key").await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), "/products?user_key=a_key");
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/transactions/authrep.xml");
+
+        let query = requests[0].parameters.query().unwrap();
+        assert!(query.contains("user_key=a_key"));
+        assert!(query.contains("usage[products]=2"));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn rejects_requests() {
+        let limited = status_xml(false, LIMITS_EXCEEDED);
+        let denied = status_xml(false, "application key is missing");
+        let invalid = r#"<error code="user_key_invalid">user key "a_key" is invalid</error>"#;
+
+        let cases = [
+            (
+                Some(limited.clone()),
+                "/products?user_key=a_key",
+                429,
+                LIMITS_EXCEEDED,
+            ),
+            (
+                Some(denied),
+                "/products?user_key=a_key",
+                403,
+                "application key is missing",
+            ),
+            (
+                Some(invalid.to_string()),
+                "/products?user_key=a_key",
+                403,
+                r#"user key "a_key" is invalid"#,
+            ),
+            (
+                None,
+                "/products?user_key=a_key",
+                503,
+                "authorization service unavailable",
+            ),
+            (
+                Some(limited.clone()),
+                "/products",
+                401,
+                "missing credentials: user_key",
+            ),
+            (
+                Some(limited),
+                "/other?user_key=a_key",
+                404,
+                "no mapping rule matched",
+            ),
+        ];
+
+        for (authorization, uri, status, body) in cases {
+            let (response, _) = send(authorization, uri).await;
+            assert_eq!(response.status().as_u16(), status, "{}", body);
+            assert_eq!(response.body(), body);
+        }
+    }
+}
@@ -1,4 +1,6 @@
 #![deny(clippy::all, clippy::cargo)]
+// Duplicate transitive dependencies are out of our control.
+#![allow(clippy::multiple_crate_versions)]
 #![deny(unsafe_op_in_unsafe_fn)]
 #![cfg_attr(feature_never_type, feature(never_type))]
 #![cfg_attr(feature_test, feature(test))]
@@ -15,27 +17,29 @@ pub(crate) mod util;
 
 pub mod api_call;
 pub mod application;
+#[cfg(feature = "xml-response-alloc")]
+pub mod cache;
+#[cfg(feature = "xml-response-alloc")]
+pub mod client;
+#[cfg(all(feature = "serde", feature = "rest-mappings", feature = "extractor"))]
+pub mod config;
 pub mod credentials;
 pub mod encoding;
 pub mod extensions;
 pub mod http;
+#[cfg(feature = "oidc")]
+pub mod oidc;
 pub mod service;
 pub mod transaction;
 pub mod usage;
 pub mod user;
 pub mod version;
 
-#[cfg(feature = "xml-response")]
+#[cfg(feature = "xml-response-alloc")]
 pub mod response;
 
-pub(crate) mod error {
-    pub use anyhow::{anyhow, Error};
-}
-
-pub use error::Error;
-
-#[allow(unused_imports)]
-pub(crate) use error::anyhow;
+mod error;
+pub use error::{ClientError, ClientErrorKind, Error, ParseError, XmlError};
 
 use std::borrow::Cow;
 
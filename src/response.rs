@@ -13,6 +13,13 @@ pub use metrics_hierarchy::MetricsHierarchy;
 mod usage_report;
 pub use usage_report::{Period, PeriodTime, UsageReport, UsageReportError, UsageReports};
 
+mod borrowed;
+pub use borrowed::{
+    AppKeysRef, AuthorizationErrorRef, AuthorizationRef, AuthorizationStatusRef, UsageReportRef,
+};
+
+mod parser;
+
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum Authorization {
@@ -39,6 +46,12 @@ impl Authorization {
             Self::Error(err) => Err(err),
         }
     }
+
+    /// Parses a response with the built-in parser, which only requires `alloc`. This is what
+    /// `from_str` uses unless the `xml-response` feature is enabled.
+    pub fn parse_xml(s: &str) -> Result<Self, crate::Error> {
+        parser::parse(s).map_err(Into::into)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
@@ -114,10 +127,16 @@ impl AuthorizationError {
 pub struct Metric(pub String);
 
 impl FromStr for Authorization {
-    type Err = serde_xml_rs::Error;
+    type Err = crate::Error;
+
+    #[cfg(feature = "xml-response")]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_xml_rs::from_str(s).map_err(|e| crate::XmlError::from(e).into())
+    }
 
+    #[cfg(not(feature = "xml-response"))]
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        serde_xml_rs::from_str(s)
+        Self::parse_xml(s)
     }
 }
 
@@ -0,0 +1,350 @@
+// Responses borrowing from the body they were parsed from.
+//
+// Most strings in a response never need escaping, so these types keep slices of the body where
+// they can and only allocate for escaped text. They can be converted into the owned types when
+// they need to outlive the body.
+use std::prelude::v1::*;
+
+use std::borrow::Cow;
+
+use super::{
+    parser, Authorization, AuthorizationError, AuthorizationStatus, ListAppKeys, MetricsHierarchy,
+    Period, PeriodTime, UsageReport, UsageReports,
+};
+
+/// An authorization borrowing from the response it was parsed from.
+///
+/// # Examples
+///
+/// ```
+/// use threescalers::response::AuthorizationRef;
+///
+/// let body = "<status><authorized>true</authorized><plan>Basic</plan></status>";
+/// let authorization = AuthorizationRef::parse(body)?;
+///
+/// assert!(authorization.into_inner().unwrap().is_authorized());
+/// # Ok::<(), threescalers::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthorizationRef<'a> {
+    Status(AuthorizationStatusRef<'a>),
+    Error(AuthorizationErrorRef<'a>),
+}
+
+impl<'a> AuthorizationRef<'a> {
+    /// Parses a response without copying the strings that need no unescaping.
+    pub fn parse(s: &'a str) -> Result<Self, crate::Error> {
+        parser::parse_borrowed(s).map_err(Into::into)
+    }
+
+    pub fn is_status(&self) -> bool {
+        matches!(self, Self::Status(_))
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Self::Error(_))
+    }
+
+    // See `Authorization::into_inner` about the meaning of the `Err` variant.
+    pub fn into_inner(self) -> Result<AuthorizationStatusRef<'a>, AuthorizationErrorRef<'a>> {
+        match self {
+            Self::Status(st) => Ok(st),
+            Self::Error(err) => Err(err),
+        }
+    }
+
+    pub fn into_owned(self) -> Authorization {
+        match self {
+            Self::Status(st) => Authorization::Status(st.into_owned()),
+            Self::Error(err) => Authorization::Error(err.into_owned()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthorizationStatusRef<'a> {
+    pub(super) authorized: bool,
+    pub(super) reason: Option<Cow<'a, str>>,
+    pub(super) plan: Cow<'a, str>,
+    pub(super) usage_reports: Option<Vec<UsageReportRef<'a>>>,
+    // Parent metrics along with their space-separated children, as found in the response.
+    pub(super) hierarchy: Option<Vec<(Cow<'a, str>, Cow<'a, str>)>>,
+    pub(super) app_keys: Option<AppKeysRef<'a>>,
+}
+
+impl AuthorizationStatusRef<'_> {
+    pub fn is_authorized(&self) -> bool {
+        self.authorized
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    pub fn authorized(&self) -> Result<(), &str> {
+        if self.authorized {
+            Ok(())
+        } else {
+            Err(self.reason().unwrap_or("unspecified reason"))
+        }
+    }
+
+    pub fn plan(&self) -> &str {
+        self.plan.as_ref()
+    }
+
+    pub fn usage_reports(&self) -> Option<&[UsageReportRef<'_>]> {
+        self.usage_reports.as_deref()
+    }
+
+    pub fn app_keys(&self) -> Option<&AppKeysRef<'_>> {
+        self.app_keys.as_ref()
+    }
+
+    /// Retrieves the parent metric of a given metric, if the response includes the hierarchy.
+    pub fn parent_of(&self, metric_name: &str) -> Option<&str> {
+        self.hierarchy
+            .as_ref()?
+            .iter()
+            .find_map(|(parent, children)| {
+                if children.split(' ').any(|child| child == metric_name) {
+                    Some(parent.as_ref())
+                } else {
+                    None
+                }
+            })
+    }
+
+    pub fn into_owned(self) -> AuthorizationStatus {
+        AuthorizationStatus {
+            authorized: self.authorized,
+            reason: self.reason.map(Cow::into_owned),
+            plan: self.plan.into_owned(),
+            usage_reports: self.usage_reports.map(|reports| {
+                UsageReports::UsageReports(
+                    reports
+                        .into_iter()
+                        .map(UsageReportRef::into_owned)
+                        .collect(),
+                )
+            }),
+            metrics_hierarchy: self.hierarchy.map(|hierarchy| {
+                hierarchy
+                    .into_iter()
+                    .fold(MetricsHierarchy::new(), |mut h, (parent, children)| {
+                        h.insert(
+                            parent.into_owned(),
+                            children
+                                .split(' ')
+                                .map(ToOwned::to_owned)
+                                .collect::<Vec<_>>(),
+                        );
+                        h
+                    })
+            }),
+            app_keys: self.app_keys.map(AppKeysRef::into_owned),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthorizationErrorRef<'a> {
+    pub(super) code: Cow<'a, str>,
+    pub(super) description: Cow<'a, str>,
+}
+
+impl AuthorizationErrorRef<'_> {
+    pub fn code(&self) -> &str {
+        self.code.as_ref()
+    }
+
+    pub fn description(&self) -> &str {
+        self.description.as_ref()
+    }
+
+    pub fn into_owned(self) -> AuthorizationError {
+        AuthorizationError {
+            code: self.code.into_owned(),
+            description: self.description.into_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageReportRef<'a> {
+    pub(super) metric: Cow<'a, str>,
+    pub(super) period: Period,
+    pub(super) period_start: PeriodTime,
+    pub(super) period_end: PeriodTime,
+    pub(super) max_value: u64,
+    pub(super) current_value: u64,
+}
+
+impl UsageReportRef<'_> {
+    pub fn metric(&self) -> &str {
+        self.metric.as_ref()
+    }
+
+    pub fn period(&self) -> &Period {
+        &self.period
+    }
+
+    pub fn period_times(&self) -> (&PeriodTime, &PeriodTime) {
+        (&self.period_start, &self.period_end)
+    }
+
+    pub fn max_value(&self) -> u64 {
+        self.max_value
+    }
+
+    pub fn current_value(&self) -> u64 {
+        self.current_value
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.max_value.saturating_sub(self.current_value)
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.current_value >= self.max_value
+    }
+
+    pub fn into_owned(self) -> UsageReport {
+        UsageReport {
+            metric: self.metric.into_owned(),
+            period: self.period,
+            period_start: self.period_start,
+            period_end: self.period_end,
+            max_value: self.max_value,
+            current_value: self.current_value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppKeysRef<'a> {
+    pub(super) service_id: Option<Cow<'a, str>>,
+    pub(super) app_id: Option<Cow<'a, str>>,
+    pub(super) keys: Vec<Cow<'a, str>>,
+}
+
+impl AppKeysRef<'_> {
+    pub fn service_id(&self) -> Option<&str> {
+        self.service_id.as_deref()
+    }
+
+    pub fn app_id(&self) -> Option<&str> {
+        self.app_id.as_deref()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.keys.iter().map(AsRef::as_ref)
+    }
+
+    pub fn into_owned(self) -> ListAppKeys {
+        ListAppKeys::new(
+            self.service_id.map(Cow::into_owned),
+            self.app_id.map(Cow::into_owned),
+            self.keys.into_iter().map(Cow::into_owned),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(super) const STATUS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<status>
+  <authorized>false</authorized>
+  <reason>usage limits are exceeded</reason>
+  <plan>Basic &amp; more</plan>
+  <usage_reports>
+    <usage_report metric="hits" period="minute">
+      <period_start>2019-06-05 16:24:00 +0000</period_start>
+      <period_end>2019-06-05 16:25:00 +0000</period_end>
+      <max_value>5</max_value>
+      <current_value>6</current_value>
+    </usage_report>
+    <usage_report metric="products" period="month">
+      <period_start>2019-06-01 00:00:00 +0200</period_start>
+      <period_end>2019-07-01 00:00:00 +0200</period_end>
+      <max_value>100</max_value>
+      <current_value>6</current_value>
+    </usage_report>
+  </usage_reports>
+  <hierarchy>
+    <metric name="hits" children="products users"/>
+  </hierarchy>
+  <app_keys app="an_app" svc="a_service">
+    <key id="a_key"/>
+    <key id="another_key"/>
+  </app_keys>
+</status>"#;
+
+    #[test]
+    fn borrows_from_the_body() {
+        let status = AuthorizationRef::parse(STATUS)
+            .unwrap()
+            .into_inner()
+            .unwrap();
+
+        assert!(matches!(status.reason, Some(Cow::Borrowed(_))));
+        // escaped text needs to be allocated
+        assert!(matches!(status.plan, Cow::Owned(_)));
+        assert_eq!(status.plan(), "Basic & more");
+
+        let reports = status.usage_reports().unwrap();
+        assert!(matches!(reports[0].metric, Cow::Borrowed("hits")));
+        assert_eq!(reports[1].period(), &Period::Month);
+        assert_eq!(reports[1].period_times().0, &PeriodTime(1559340000));
+        assert!(reports[0].is_limited());
+
+        assert_eq!(status.parent_of("users"), Some("hits"));
+        assert_eq!(status.parent_of("hits"), None);
+
+        let app_keys = status.app_keys().unwrap();
+        assert_eq!(app_keys.app_id(), Some("an_app"));
+        assert_eq!(
+            app_keys.keys().collect::<Vec<_>>(),
+            ["a_key", "another_key"]
+        );
+    }
+
+    #[test]
+    fn same_results_as_owned() {
+        let error = r#"<error code="user_key_invalid">user key "x" is invalid</error>"#;
+
+        for xml in [STATUS, error] {
+            let borrowed = AuthorizationRef::parse(xml).unwrap();
+            assert_eq!(borrowed.into_owned(), xml.parse::<Authorization>().unwrap());
+        }
+    }
+}
+
+#[cfg(all(test, feature_test))]
+mod benches {
+    use super::*;
+    use core::str::FromStr;
+    use test::Bencher;
+
+    #[bench]
+    fn bench_from_str(b: &mut Bencher) {
+        b.iter(|| Authorization::from_str(tests::STATUS))
+    }
+
+    #[bench]
+    fn bench_parse_xml(b: &mut Bencher) {
+        b.iter(|| Authorization::parse_xml(tests::STATUS))
+    }
+
+    #[bench]
+    fn bench_parse_borrowed(b: &mut Bencher) {
+        b.iter(|| AuthorizationRef::parse(tests::STATUS))
+    }
+
+    #[bench]
+    fn bench_parse_borrowed_into_owned(b: &mut Bencher) {
+        b.iter(|| AuthorizationRef::parse(tests::STATUS).map(AuthorizationRef::into_owned))
+    }
+}
@@ -0,0 +1,515 @@
+// A parser for the subset of XML used in Apisonator responses, which only needs `alloc`.
+//
+// Documents are read into a small tree of elements borrowing from the input first, and then mapped
+// onto the borrowed response types, which can in turn be converted into the owned ones. Unknown
+// elements and attributes are ignored, as the serde-based parser does. DTDs,
+// processing instructions other than the XML declaration and namespaces are not supported, since
+// Apisonator does not use them.
+use std::prelude::v1::*;
+
+use std::borrow::Cow;
+
+use super::{
+    AppKeysRef, Authorization, AuthorizationErrorRef, AuthorizationRef, AuthorizationStatusRef,
+    PeriodTime, UsageReportRef,
+};
+use crate::XmlError;
+
+#[derive(Debug)]
+struct Element<'a> {
+    name: &'a str,
+    // byte offset of the element in the document, to locate errors
+    offset: usize,
+    attributes: Vec<(&'a str, Cow<'a, str>)>,
+    children: Vec<Element<'a>>,
+    text: Cow<'a, str>,
+}
+
+impl<'a> Element<'a> {
+    fn attribute(&self, name: &str) -> Option<&Cow<'a, str>> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v)
+    }
+
+    // Whitespace around children is not part of the text, which avoids concatenating it.
+    fn push_text(&mut self, text: Cow<'a, str>) {
+        let blank = text.trim().is_empty();
+
+        if self.text.is_empty() || (self.text.trim().is_empty() && !blank) {
+            self.text = text;
+        } else if !blank || self.children.is_empty() {
+            self.text.to_mut().push_str(&text);
+        }
+    }
+
+    fn push_child(&mut self, child: Element<'a>) {
+        if self.text.trim().is_empty() {
+            self.text = Cow::Borrowed("");
+        }
+        self.children.push(child);
+    }
+
+    fn child(&self, name: &str) -> Option<&Element<'a>> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    fn children<'s>(&'s self, name: &'s str) -> impl Iterator<Item = &'s Element<'a>> + 's {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+}
+
+struct Reader<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, pos: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn error_at<S: Into<String>>(&self, offset: usize, message: S) -> XmlError {
+        let before = &self.input[..offset.min(self.input.len())];
+        let line = before.matches('\n').count() as u64 + 1;
+        let column = before
+            .rfind('\n')
+            .map_or(before, |nl| &before[nl + 1..])
+            .chars()
+            .count() as u64
+            + 1;
+
+        XmlError::new(message).at(line, column)
+    }
+
+    fn error<S: Into<String>>(&self, message: S) -> XmlError {
+        self.error_at(self.pos, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), XmlError> {
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {:?}", token)))
+        }
+    }
+
+    // Skips up to and including `end`.
+    fn skip_past(&mut self, end: &str) -> Result<(), XmlError> {
+        match self.rest().find(end) {
+            Some(idx) => {
+                self.pos += idx + end.len();
+                Ok(())
+            }
+            None => Err(self.error(format!("unterminated markup, expected {:?}", end))),
+        }
+    }
+
+    // Skips whitespace, comments and processing instructions.
+    fn skip_misc(&mut self) -> Result<(), XmlError> {
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if self.rest().starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<&'a str, XmlError> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '=' | '<'))
+            .unwrap_or(rest.len());
+
+        if len == 0 {
+            return Err(self.error("expected a name"));
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    fn document(mut self) -> Result<Element<'a>, XmlError> {
+        self.skip_misc()?;
+        let root = self.element()?;
+        self.skip_misc()?;
+
+        if self.pos < self.input.len() {
+            return Err(self.error("unexpected content after the root element"));
+        }
+
+        Ok(root)
+    }
+
+    fn element(&mut self) -> Result<Element<'a>, XmlError> {
+        let offset = self.pos;
+        self.expect("<")?;
+        let name = self.name()?;
+        let mut element = Element {
+            name,
+            offset,
+            attributes: Vec::new(),
+            children: Vec::new(),
+            text: Cow::Borrowed(""),
+        };
+
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("/>") {
+                self.pos += 2;
+                return Ok(element);
+            }
+            if self.rest().starts_with('>') {
+                self.pos += 1;
+                break;
+            }
+
+            let name = self.name()?;
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+            let quote = match self.rest().chars().next() {
+                Some(q @ ('"' | '\'')) => q,
+                _ => return Err(self.error("expected a quoted attribute value")),
+            };
+            self.pos += 1;
+            let len = self
+                .rest()
+                .find(quote)
+                .ok_or_else(|| self.error("unterminated attribute value"))?;
+            let value = self.unescape(self.pos, &self.rest()[..len])?;
+            self.pos += len + 1;
+            element.attributes.push((name, value));
+        }
+
+        loop {
+            let rest = self.rest();
+            if rest.starts_with("</") {
+                let start = self.pos;
+                self.pos += 2;
+                let end = self.name()?;
+                if end != name {
+                    return Err(self.error_at(
+                        start,
+                        format!("expected closing tag for {:?}, found {:?}", name, end),
+                    ));
+                }
+                self.skip_whitespace();
+                self.expect(">")?;
+                return Ok(element);
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+                let len = cdata
+                    .find("]]>")
+                    .ok_or_else(|| self.error("unterminated CDATA section"))?;
+                element.push_text(Cow::Borrowed(&cdata[..len]));
+                self.pos += "<![CDATA[".len() + len + "]]>".len();
+            } else if rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if rest.starts_with('<') {
+                let child = self.element()?;
+                element.push_child(child);
+            } else if rest.is_empty() {
+                return Err(self.error(format!("unexpected end of document in {:?}", name)));
+            } else {
+                let len = rest.find('<').unwrap_or(rest.len());
+                let text = self.unescape(self.pos, &rest[..len])?;
+                element.push_text(text);
+                self.pos += len;
+            }
+        }
+    }
+
+    fn unescape(&self, offset: usize, s: &'a str) -> Result<Cow<'a, str>, XmlError> {
+        if !s.contains('&') {
+            return Ok(Cow::Borrowed(s));
+        }
+
+        let mut out = String::with_capacity(s.len());
+        let mut rest = s;
+        while let Some(idx) = rest.find('&') {
+            out.push_str(&rest[..idx]);
+            let entity_offset = offset + (s.len() - rest.len()) + idx;
+            let end = rest[idx..]
+                .find(';')
+                .ok_or_else(|| self.error_at(entity_offset, "unterminated entity"))?;
+            let entity = &rest[idx + 1..idx + end];
+            let c = match entity {
+                "lt" => '<',
+                "gt" => '>',
+                "amp" => '&',
+                "quot" => '"',
+                "apos" => '\'',
+                _ => entity
+                    .strip_prefix("#x")
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| entity.strip_prefix('#').map(str::parse))
+                    .and_then(Result::ok)
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| {
+                        self.error_at(entity_offset, format!("unknown entity {:?}", entity))
+                    })?,
+            };
+            out.push(c);
+            rest = &rest[idx + end + 1..];
+        }
+        out.push_str(rest);
+
+        Ok(Cow::Owned(out))
+    }
+}
+
+// Maps the element tree onto the borrowed response types.
+struct Mapper<'a> {
+    reader: Reader<'a>,
+}
+
+impl<'a> Mapper<'a> {
+    fn missing(&self, element: &Element, what: &str) -> XmlError {
+        self.reader.error_at(
+            element.offset,
+            format!("missing {} in {:?}", what, element.name),
+        )
+    }
+
+    fn required<'e>(
+        &self,
+        element: &'e Element<'a>,
+        name: &str,
+    ) -> Result<&'e Element<'a>, XmlError> {
+        element
+            .child(name)
+            .ok_or_else(|| self.missing(element, name))
+    }
+
+    fn attribute(&self, element: &Element<'a>, name: &str) -> Result<Cow<'a, str>, XmlError> {
+        element
+            .attribute(name)
+            .cloned()
+            .ok_or_else(|| self.missing(element, name))
+    }
+
+    fn number(&self, element: &Element, name: &str) -> Result<u64, XmlError> {
+        let child = self.required(element, name)?;
+        child.text.trim().parse().map_err(|_| {
+            self.reader
+                .error_at(child.offset, format!("invalid number {:?}", child.text))
+        })
+    }
+
+    fn time(&self, element: &Element, name: &str) -> Result<PeriodTime, XmlError> {
+        let child = self.required(element, name)?;
+        PeriodTime::parse(child.text.trim()).map_err(|e| self.reader.error_at(child.offset, e))
+    }
+
+    fn authorization(&self, root: Element<'a>) -> Result<AuthorizationRef<'a>, XmlError> {
+        match root.name {
+            "status" => self.status(root).map(AuthorizationRef::Status),
+            "error" => Ok(AuthorizationRef::Error(AuthorizationErrorRef {
+                code: self.attribute(&root, "code")?,
+                description: root.text,
+            })),
+            other => Err(self
+                .reader
+                .error_at(root.offset, format!("unexpected root element {:?}", other))),
+        }
+    }
+
+    fn status(&self, mut root: Element<'a>) -> Result<AuthorizationStatusRef<'a>, XmlError> {
+        let authorized = self.required(&root, "authorized")?;
+        let authorized = match authorized.text.trim() {
+            "true" => true,
+            "false" => false,
+            other => {
+                return Err(self
+                    .reader
+                    .error_at(authorized.offset, format!("invalid boolean {:?}", other)))
+            }
+        };
+        self.required(&root, "plan")?;
+
+        let mut status = AuthorizationStatusRef {
+            authorized,
+            reason: None,
+            plan: Cow::Borrowed(""),
+            usage_reports: None,
+            hierarchy: None,
+            app_keys: None,
+        };
+
+        // the first occurrence of each element wins, like `Element::child`
+        for child in core::mem::take(&mut root.children).into_iter().rev() {
+            match child.name {
+                "reason" => status.reason = Some(child.text),
+                "plan" => status.plan = child.text,
+                "usage_reports" => {
+                    status.usage_reports = Some(
+                        child
+                            .children("usage_report")
+                            .map(|report| self.usage_report(report))
+                            .collect::<Result<Vec<_>, _>>()?,
+                    )
+                }
+                "hierarchy" => {
+                    status.hierarchy = Some(
+                        child
+                            .children("metric")
+                            .map(|metric| {
+                                Ok((
+                                    self.attribute(metric, "name")?,
+                                    self.attribute(metric, "children")?,
+                                ))
+                            })
+                            .collect::<Result<Vec<_>, XmlError>>()?,
+                    )
+                }
+                "app_keys" => {
+                    status.app_keys = Some(AppKeysRef {
+                        app_id: child.attribute("app").cloned(),
+                        service_id: child.attribute("svc").cloned(),
+                        keys: child
+                            .children("key")
+                            .map(|key| self.attribute(key, "id"))
+                            .collect::<Result<Vec<_>, _>>()?,
+                    })
+                }
+                _ => (),
+            }
+        }
+
+        Ok(status)
+    }
+
+    fn usage_report(&self, report: &Element<'a>) -> Result<UsageReportRef<'a>, XmlError> {
+        Ok(UsageReportRef {
+            metric: self.attribute(report, "metric")?,
+            period: self.attribute(report, "period")?.as_ref().into(),
+            period_start: self.time(report, "period_start")?,
+            period_end: self.time(report, "period_end")?,
+            max_value: self.number(report, "max_value")?,
+            current_value: self.number(report, "current_value")?,
+        })
+    }
+}
+
+/// Parses an Apisonator XML response, borrowing from it where possible.
+pub(super) fn parse_borrowed(input: &str) -> Result<AuthorizationRef<'_>, XmlError> {
+    let root = Reader::new(input).document()?;
+
+    Mapper {
+        reader: Reader::new(input),
+    }
+    .authorization(root)
+}
+
+/// Parses an Apisonator XML response.
+pub(super) fn parse(input: &str) -> Result<Authorization, XmlError> {
+    parse_borrowed(input).map(AuthorizationRef::into_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::{ListAppKeys, Period};
+
+    #[test]
+    fn parse_status() {
+        let xml = r##"<?xml version="1.0" encoding="UTF-8"?>
+        <!-- a comment -->
+        <status>
+            <authorized>false</authorized>
+            <reason>usage limits are exceeded &amp; more</reason>
+            <plan><![CDATA[Basic <plan>]]></plan>
+            <usage_reports>
+                <usage_report metric="hits" period='minute' exceeded="true">
+                    <period_start>2019-06-05 16:24:00 +0000</period_start>
+                    <period_end>2019-06-05 16:25:00 +0000</period_end>
+                    <max_value>5</max_value>
+                    <current_value>6</current_value>
+                </usage_report>
+            </usage_reports>
+            <hierarchy>
+                <metric name="hits" children="products users" />
+            </hierarchy>
+            <app_keys app="an_app" svc="a_service"><key id="k&#49;"/></app_keys>
+            <unknown><nested/></unknown>
+        </status>
+        "##;
+
+        let status = parse(xml).unwrap().into_inner().unwrap();
+        assert!(!status.is_authorized());
+        assert_eq!(status.reason(), Some("usage limits are exceeded & more"));
+        assert_eq!(status.plan(), "Basic <plan>");
+
+        let report = &status.usage_reports().unwrap()[0];
+        assert_eq!(report.metric(), "hits");
+        assert_eq!(report.period(), &Period::Minute);
+        assert_eq!(report.period_times().0, &PeriodTime(1559751840));
+        assert_eq!(report.current_value(), 6);
+
+        assert_eq!(status.hierarchy().unwrap().parent_of("users"), Some("hits"));
+        assert_eq!(
+            status.app_keys(),
+            Some(&ListAppKeys::new(Some("a_service"), Some("an_app"), ["k1"]))
+        );
+    }
+
+    #[test]
+    fn parse_error() {
+        let xml = r#"<error code="user_key_invalid">user key "x" is invalid</error>"#;
+        let error = parse(xml).unwrap().into_inner().unwrap_err();
+
+        assert_eq!(error.code(), "user_key_invalid");
+        assert_eq!(error.description(), r#"user key "x" is invalid"#);
+    }
+
+    #[test]
+    fn locate_errors() {
+        let cases: [(&str, (u64, u64)); 5] = [
+            ("<status>\n  <authorized>true</status>", (2, 19)),
+            ("<status><plan>P</plan></status>", (1, 1)),
+            (
+                "<status>\n<authorized>maybe</authorized><plan/></status>",
+                (2, 1),
+            ),
+            ("<error>&bogus;</error>", (1, 8)),
+            ("<status/><status/>", (1, 10)),
+        ];
+
+        for (xml, (line, column)) in cases {
+            let e = parse(xml).unwrap_err();
+            assert_eq!((e.line(), e.column()), (Some(line), Some(column)), "{}", e);
+        }
+    }
+
+    #[cfg(feature = "xml-response")]
+    #[test]
+    fn same_results_as_serde() {
+        let documents = [
+            r#"<status><authorized>true</authorized><plan>Basic</plan></status>"#,
+            r#"<status><authorized>true</authorized><plan>Basic</plan><usage_reports><usage_report metric="hits" period="eternity"><period_start>2019-06-05 16:24:00 +0000</period_start><period_end>2019-06-05 16:25:00 +0000</period_end><max_value>5</max_value><current_value>0</current_value></usage_report></usage_reports></status>"#,
+            r#"<status><authorized>true</authorized><plan>B</plan><hierarchy><metric name="a" children="b c"/></hierarchy><app_keys app="x" svc="y"><key id="z"/></app_keys></status>"#,
+            r#"<error code="provider_key_invalid">provider key is invalid</error>"#,
+        ];
+
+        for xml in documents {
+            let serde: Authorization = serde_xml_rs::from_str(xml).unwrap();
+            assert_eq!(parse(xml).unwrap(), serde);
+        }
+    }
+}
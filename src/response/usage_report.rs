@@ -155,15 +155,21 @@ impl Visitor<'_> for PeriodStringVisitor {
     where
         E: de::Error,
     {
-        match v {
-            "minute" => Ok(Period::Minute),
-            "hour" => Ok(Period::Hour),
-            "day" => Ok(Period::Day),
-            "week" => Ok(Period::Week),
-            "month" => Ok(Period::Month),
-            "year" => Ok(Period::Year),
-            "eternity" => Ok(Period::Eternity),
-            period_name => Ok(Period::Other(period_name.into())),
+        Ok(Period::from(v))
+    }
+}
+
+impl From<&str> for Period {
+    fn from(name: &str) -> Self {
+        match name {
+            "minute" => Period::Minute,
+            "hour" => Period::Hour,
+            "day" => Period::Day,
+            "week" => Period::Week,
+            "month" => Period::Month,
+            "year" => Period::Year,
+            "eternity" => Period::Eternity,
+            period_name => Period::Other(period_name.into()),
         }
     }
 }
@@ -197,15 +203,85 @@ impl<'de> Visitor<'de> for TimestampVisitor {
         let _key: Option<String> = map.next_key()?;
         let timestamp: String = map.next_value()?;
 
-        let ts_str = timestamp.as_str();
-        let dt = DateTime::parse_from_str(ts_str, "%Y-%m-%d %H:%M:%S %z").map_err(|e| {
-            de::Error::custom(format_args!(
-                "invalid timestamp {}, expected %Y-%m-%d %H:%M:%S %z: {:?}",
-                ts_str, e
-            ))
-        })?;
+        PeriodTime::parse(timestamp.as_str()).map_err(de::Error::custom)
+    }
+}
+
+impl PeriodTime {
+    /// Parses a timestamp in the format used by Apisonator, ie. `2019-06-05 16:24:00 +0000`.
+    pub fn parse(timestamp: &str) -> Result<Self, String> {
+        if let Some(period_time) = Self::parse_exact(timestamp.as_bytes()) {
+            return Ok(period_time);
+        }
+
+        DateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S %z")
+            .map(PeriodTime::from)
+            .map_err(|e| {
+                format!(
+                    "invalid timestamp {}, expected %Y-%m-%d %H:%M:%S %z: {:?}",
+                    timestamp, e
+                )
+            })
+    }
+
+    // Fast path for timestamps written exactly as Apisonator does. Anything else, including
+    // values chrono would still accept, such as other widths or leap seconds, is left to chrono.
+    fn parse_exact(ts: &[u8]) -> Option<Self> {
+        fn digits(ts: &[u8], range: core::ops::Range<usize>) -> Option<i64> {
+            ts.get(range)?.iter().try_fold(0, |acc, &b| {
+                b.is_ascii_digit().then(|| acc * 10 + i64::from(b - b'0'))
+            })
+        }
+
+        if ts.len() != 25
+            || ts[4] != b'-'
+            || ts[7] != b'-'
+            || ts[10] != b' '
+            || ts[13] != b':'
+            || ts[16] != b':'
+            || ts[19] != b' '
+        {
+            return None;
+        }
+
+        let (year, month, day) = (digits(ts, 0..4)?, digits(ts, 5..7)?, digits(ts, 8..10)?);
+        let (hour, min, sec) = (
+            digits(ts, 11..13)?,
+            digits(ts, 14..16)?,
+            digits(ts, 17..19)?,
+        );
+        let (off_hour, off_min) = (digits(ts, 21..23)?, digits(ts, 23..25)?);
+        let sign = match ts[20] {
+            b'+' => 1,
+            b'-' => -1,
+            _ => return None,
+        };
+
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let month_days = match month {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            1..=12 => 31,
+            _ => return None,
+        };
+        if year == 0 || !(1..=month_days).contains(&day) || hour > 23 || min > 59 || sec > 59 {
+            return None;
+        }
+        if off_hour > 23 || off_min > 59 {
+            return None;
+        }
+
+        // days since the epoch of a date in the proleptic Gregorian calendar
+        let y = if month <= 2 { year - 1 } else { year };
+        let (era, yoe) = (y / 400, y % 400);
+        let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+        let days = era * 146_097 + yoe * 365 + yoe / 4 - yoe / 100 + doy - 719_468;
 
-        Ok(PeriodTime::from(dt))
+        let offset = sign * (off_hour * 3600 + off_min * 60);
+        Some(PeriodTime(
+            days * 86_400 + hour * 3600 + min * 60 + sec - offset,
+        ))
     }
 }
 
@@ -251,6 +327,39 @@ mod tests {
         assert_eq!(ur.current_value(), 0);
     }
 
+    #[test]
+    fn test_period_time_parse() {
+        let timestamps = [
+            "2019-06-05 16:24:00 +0000",
+            "2020-02-29 23:59:59 -0130",
+            "1969-12-31 23:59:59 +0000",
+            "0001-03-01 00:00:00 +2359",
+            "2100-12-31 12:00:00 +0100",
+        ];
+
+        for ts in timestamps {
+            let chrono = DateTime::parse_from_str(ts, "%Y-%m-%d %H:%M:%S %z").unwrap();
+            assert_eq!(
+                PeriodTime::parse_exact(ts.as_bytes()),
+                Some(chrono.into()),
+                "{}",
+                ts
+            );
+        }
+
+        // left to chrono
+        assert!(PeriodTime::parse_exact(b"2019-06-05 16:24:00 +00:00").is_none());
+        assert!(PeriodTime::parse("2019-06-05 16:24:00 +00:00").is_ok());
+        for invalid in [
+            "2019-02-29 00:00:00 +0000",
+            "2019-06-05 24:00:00 +0000",
+            "2019-06-05",
+        ] {
+            assert!(PeriodTime::parse_exact(invalid.as_bytes()).is_none());
+            assert!(PeriodTime::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
     #[test]
     fn test_usage_report_counters() {
         let mut ur = sample_usage_report();
@@ -278,6 +387,7 @@ mod tests {
         assert_eq!(auth_err, UsageReportError::Overflow);
     }
 
+    #[cfg(feature = "xml-response")]
     #[test]
     fn test_deserialization() {
         let xml = r#"
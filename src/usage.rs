@@ -11,6 +11,16 @@ impl<'m, M: AsRef<str> + 'm, V: AsRef<str> + 'm> From<&'m (M, V)> for MetricUsag
     }
 }
 
+impl<'m> MetricUsage<'m> {
+    pub fn metric(&self) -> &'m str {
+        self.0
+    }
+
+    pub fn value(&self) -> &'m str {
+        self.1
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Usage<'m>(Vec<MetricUsage<'m>>);
 
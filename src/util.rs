@@ -4,3 +4,8 @@ pub mod compat;
 pub use compat::features::Never;
 
 pub mod string;
+
+// not every feature set has tests using all of these
+#[cfg(test)]
+#[allow(dead_code)]
+pub mod fixtures;
@@ -0,0 +1,27 @@
+use std::prelude::v1::*;
+
+use crate::{
+    api_call::{ApiCall, Kind},
+    application::Application,
+    credentials::Credentials,
+    http::Request,
+    service::Service,
+    transaction::Transaction,
+};
+
+pub fn service() -> Service {
+    Service::new("a_service", Credentials::from_token("a_token"))
+}
+
+/// Runs `f` with a call of the given kind for a single application identified by a user key.
+pub fn with_apicall<R>(kind: Kind, f: impl FnOnce(&ApiCall) -> R) -> R {
+    let service = service();
+    let app = Application::from_user_key("a_user_key");
+    let txns = [Transaction::new(&app, None, None, None)];
+
+    f(&ApiCall::new(kind, &service, &txns, None))
+}
+
+pub fn request(kind: Kind) -> Request {
+    with_apicall(kind, |apicall| Request::from(apicall))
+}
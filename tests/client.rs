@@ -0,0 +1,143 @@
+#![cfg(any(
+    feature = "curl-easy",
+    feature = "reqwest-sync",
+    feature = "reqwest-async",
+    feature = "hyper",
+    feature = "ureq"
+))]
+
+mod common;
+
+use std::net::TcpListener;
+
+use threescalers::{api_call::Kind, http::Backend, ClientErrorKind, Error};
+
+use common::{serve_once, with_apicall};
+
+// A backend on a port nothing listens on.
+fn refusing_backend() -> Backend {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    format!("http://127.0.0.1:{}", port).parse().unwrap()
+}
+
+fn assert_connect_error(e: Error) {
+    assert!(e.is_transport(), "{:?}", e);
+    assert!(
+        matches!(&e, Error::Client(e) if e.kind() == ClientErrorKind::Connect),
+        "{:?}",
+        e
+    );
+}
+
+#[cfg(any(feature = "curl-easy", feature = "reqwest-sync", feature = "ureq"))]
+mod blocking {
+    use super::*;
+    use threescalers::client::{BlockingClient, BlockingTransport, Response};
+
+    const AUTHORIZED: &str = r#"<?xml version="1.0" encoding="UTF-8"?><status><authorized>true</authorized><plan>Basic</plan></status>"#;
+
+    fn assert_authorized(response: Response) {
+        let authorization = response.into_authorization().unwrap();
+        assert!(authorization.into_inner().unwrap().is_authorized());
+    }
+
+    fn authorize_and_report<T: BlockingTransport>(transport: T) {
+        let (backend, server) = serve_once("200 OK", AUTHORIZED);
+        let mut client = BlockingClient::new(transport, backend);
+        let response = with_apicall(Kind::Authorize, |apicall| client.call(apicall)).unwrap();
+        assert!(server
+            .join()
+            .unwrap()
+            .starts_with("GET /transactions/authorize.xml?"));
+        assert_authorized(response);
+
+        let (backend, server) = serve_once("202 Accepted", "");
+        let mut client = BlockingClient::new(client.into_inner(), backend);
+        let response = with_apicall(Kind::Report, |apicall| client.call(apicall)).unwrap();
+        assert!(server
+            .join()
+            .unwrap()
+            .starts_with("POST /transactions.xml HTTP/1.1\r\n"));
+        assert!(response.is_accepted());
+
+        let mut client = BlockingClient::new(client.into_inner(), refusing_backend());
+        let e = with_apicall(Kind::Authorize, |apicall| client.call(apicall)).unwrap_err();
+        assert_connect_error(e);
+    }
+
+    #[cfg(feature = "curl-easy")]
+    #[test]
+    fn curl_easy() {
+        authorize_and_report(curl::easy::Easy::new());
+    }
+
+    #[cfg(feature = "reqwest-sync")]
+    #[test]
+    fn reqwest_blocking() {
+        authorize_and_report(reqwest::blocking::Client::new());
+    }
+
+    #[cfg(feature = "ureq")]
+    #[test]
+    fn ureq_agent() {
+        authorize_and_report(ureq::Agent::new());
+    }
+}
+
+#[cfg(any(feature = "hyper", feature = "reqwest-async"))]
+mod non_blocking {
+    use super::*;
+    use threescalers::{
+        client::{Client, Response, Transport},
+        http::Request,
+    };
+
+    // Futures of Client::call borrow the call, so they are sent as requests instead.
+    async fn call<T: Transport>(client: &Client<T>, kind: Kind) -> Result<Response, Error> {
+        let request = with_apicall(kind, |apicall| Request::from(apicall));
+        let response = client.send(request).await?;
+
+        Response::parse(kind, &response)
+    }
+
+    async fn authorize_and_report<T: Transport>(transport: T) {
+        let (backend, server) = serve_once("409 Conflict", "<status><authorized>false</authorized><reason>usage limits are exceeded</reason><plan>Basic</plan></status>");
+        let client = Client::new(transport, backend);
+        let response = call(&client, Kind::AuthRep).await.unwrap();
+        assert!(server
+            .join()
+            .unwrap()
+            .starts_with("GET /transactions/authrep.xml?"));
+        let status = response.into_authorization().unwrap().into_inner().unwrap();
+        assert!(!status.is_authorized());
+
+        let (backend, server) = serve_once("202 Accepted", "");
+        let client = Client::new(client.into_inner(), backend);
+        assert!(call(&client, Kind::Report).await.unwrap().is_accepted());
+        assert!(server
+            .join()
+            .unwrap()
+            .starts_with("POST /transactions.xml HTTP/1.1\r\n"));
+
+        let client = Client::new(client.into_inner(), refusing_backend());
+        assert_connect_error(call(&client, Kind::Report).await.unwrap_err());
+    }
+
+    #[cfg(feature = "hyper")]
+    #[tokio::test(flavor = "current_thread")]
+    async fn hyper_client() {
+        use hyper_util::{client::legacy::Client as HyperClient, rt::TokioExecutor};
+        use threescalers::http::request::hyper::Body;
+
+        let client = HyperClient::builder(TokioExecutor::new()).build_http::<Body>();
+        authorize_and_report(client).await;
+    }
+
+    #[cfg(feature = "reqwest-async")]
+    #[tokio::test(flavor = "current_thread")]
+    async fn reqwest_client() {
+        authorize_and_report(reqwest::Client::new()).await;
+    }
+}
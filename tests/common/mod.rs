@@ -0,0 +1,72 @@
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    thread,
+};
+
+use threescalers::{
+    api_call::{ApiCall, Kind},
+    application::Application,
+    credentials::Credentials,
+    http::Backend,
+    service::Service,
+    transaction::Transaction,
+};
+
+/// Runs `f` with a call of the given kind for a single application identified by a user key.
+pub fn with_apicall<R>(kind: Kind, f: impl FnOnce(&ApiCall) -> R) -> R {
+    let service = Service::new("a_service_id", Credentials::from_token("a_token"));
+    let app = Application::from_user_key("a_user_key");
+    let txns = [Transaction::new(&app, None, None, None)];
+
+    f(&ApiCall::new(kind, &service, &txns, None))
+}
+
+// Serves a single connection with the given status and body, returning the whole request.
+pub fn serve_once(
+    status: &'static str,
+    body: &'static str,
+) -> (Backend, thread::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+
+        // read the head and then as much body as declared
+        let body_len = loop {
+            let n = stream.read(&mut buf).unwrap();
+            assert!(n > 0, "connection closed before the end of the request");
+            request.extend_from_slice(&buf[..n]);
+
+            let text = String::from_utf8_lossy(&request).to_ascii_lowercase();
+            if let Some(idx) = text.find("\r\n\r\n") {
+                let len = text[..idx]
+                    .lines()
+                    .find_map(|l| l.strip_prefix("content-length: "))
+                    .map_or(0, |l| l.trim().parse::<usize>().unwrap());
+                break idx + 4 + len;
+            }
+        };
+        while request.len() < body_len {
+            let n = stream.read(&mut buf).unwrap();
+            request.extend_from_slice(&buf[..n]);
+        }
+
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).unwrap();
+
+        String::from_utf8(request).unwrap()
+    });
+
+    let backend = format!("http://127.0.0.1:{}", port).parse().unwrap();
+
+    (backend, server)
+}
@@ -0,0 +1,62 @@
+#![cfg(feature = "hyper")]
+
+mod common;
+
+use hyper_util::{client::legacy::Client, rt::TokioExecutor};
+use threescalers::{
+    api_call::Kind,
+    http::{
+        request::hyper::{authorize, Body},
+        Request,
+    },
+    response::Authorization,
+    Error,
+};
+
+use common::{serve_once, with_apicall};
+
+async fn authorize_against(
+    status: &'static str,
+    body: &'static str,
+) -> (String, Result<Authorization, Error>) {
+    let (backend, server) = serve_once(status, body);
+    let backend = backend.with_prefix("/3scale");
+    let request = with_apicall(Kind::Authorize, |apicall| Request::from(apicall));
+
+    let client = Client::builder(TokioExecutor::new()).build_http::<Body>();
+    let result = authorize(&client, request, &backend).await;
+
+    (server.join().unwrap(), result)
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn authorize_through_hyper() {
+    let (request, result) = authorize_against(
+        "200 OK",
+        r#"<?xml version="1.0" encoding="UTF-8"?><status><authorized>true</authorized><plan>Basic</plan></status>"#,
+    )
+    .await;
+
+    assert!(request.starts_with("GET /3scale/transactions/authorize.xml?"));
+    assert!(request.contains("user_key=a_user_key"));
+    assert!(request
+        .to_ascii_lowercase()
+        .contains("accept: application/xml"));
+
+    let status = result.unwrap().into_inner().unwrap();
+    assert!(status.is_authorized());
+    assert_eq!(status.plan(), "Basic");
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn denied_and_invalid_responses() {
+    let (_, result) = authorize_against(
+        "403 Forbidden",
+        r#"<?xml version="1.0" encoding="UTF-8"?><error code="user_key_invalid">invalid</error>"#,
+    )
+    .await;
+    assert!(result.unwrap().is_error());
+
+    let (_, result) = authorize_against("502 Bad Gateway", "").await;
+    assert!(result.unwrap_err().to_string().contains("502"));
+}
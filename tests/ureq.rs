@@ -0,0 +1,63 @@
+#![cfg(feature = "ureq")]
+
+mod common;
+
+use threescalers::{
+    api_call::Kind,
+    http::{
+        request::{HeaderPolicy, SetupRequest},
+        Request,
+    },
+};
+
+use common::{serve_once, with_apicall};
+
+#[test]
+fn authorize_through_ureq() {
+    let (backend, server) = serve_once(
+        "409 Conflict",
+        r#"<?xml version="1.0" encoding="UTF-8"?><status><authorized>false</authorized><reason>usage limits are exceeded</reason><plan>Basic</plan></status>"#,
+    );
+
+    let policy = HeaderPolicy::new()
+        .header("X-Trace", "a")
+        .unwrap()
+        .header("X-Trace", "b")
+        .unwrap();
+    let request = with_apicall(Kind::Authorize, |apicall| {
+        Request::with_policy(apicall, &policy)
+    });
+
+    let mut agent = ureq::Agent::new();
+    let request = agent.setup_request(request, &backend).unwrap();
+    assert!(request.body().is_none());
+
+    let authorization = request.authorize().unwrap();
+    let request = server.join().unwrap();
+
+    assert!(request.starts_with("GET /transactions/authorize.xml?"));
+    assert!(request.contains("\r\nX-Trace: a, b\r\n"));
+
+    let status = authorization.into_inner().unwrap();
+    assert!(!status.is_authorized());
+    assert_eq!(status.reason(), Some("usage limits are exceeded"));
+}
+
+#[test]
+fn report_through_ureq() {
+    let (backend, server) = serve_once("202 Accepted", "");
+
+    let request = with_apicall(Kind::Report, |apicall| Request::from(apicall));
+
+    let mut agent = ureq::Agent::new();
+    let request = agent.setup_request(request, &backend).unwrap();
+    let response = request.call().unwrap();
+    assert_eq!(response.status(), 202);
+
+    let request = server.join().unwrap();
+    assert!(request.starts_with("POST /transactions.xml HTTP/1.1\r\n"));
+    assert!(request.contains("\r\nContent-Type: application/x-www-form-urlencoded\r\n"));
+    let (_, body) = request.split_once("\r\n\r\n").unwrap();
+    assert!(body.contains("service_id=a_service_id"));
+    assert!(body.contains("user_key=a_user_key"));
+}